@@ -0,0 +1,96 @@
+use std::{
+    sync::atomic::{AtomicI64, Ordering},
+    thread,
+    thread::sleep,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use log::info;
+use rust_i18n::t;
+use serde::{Deserialize, Serialize};
+
+// 两次检查之间的墙钟间隔超过该值时，认为 Mac 刚从睡眠中唤醒
+const WAKE_GAP_SECS: i64 = 10;
+
+// 最近一次启动或唤醒的时间（unix 秒）
+static ACTIVATED_AT: AtomicI64 = AtomicI64::new(0);
+
+// 启动或唤醒时，对在此之前到达的信息的处理策略
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum CatchUpPolicy {
+    // 忽略早于 catch_up_max_age 秒的信息，较新的只在悬浮窗中展示，不会粘贴到启动时的前台窗口
+    #[default]
+    Ignore,
+    // 只在悬浮窗中展示，不自动粘贴
+    Show,
+    // 与正常到达的信息一样处理
+    Process,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CatchUpAction {
+    Deliver,
+    ShowOnly,
+    Skip,
+}
+
+pub fn default_catch_up_max_age() -> u64 {
+    30
+}
+
+pub fn unix_now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+pub fn mark_activated(at: i64) {
+    ACTIVATED_AT.store(at, Ordering::SeqCst);
+}
+
+pub fn activated_at() -> i64 {
+    ACTIVATED_AT.load(Ordering::SeqCst)
+}
+
+// 判断一条信息在当前策略下应如何处理，received_at 为信息到达时间（unix 秒）
+pub fn catch_up_action(
+    policy: CatchUpPolicy,
+    max_age: u64,
+    received_at: i64,
+    activated_at: i64,
+    now: i64,
+) -> CatchUpAction {
+    if received_at >= activated_at {
+        return CatchUpAction::Deliver;
+    }
+    match policy {
+        CatchUpPolicy::Process => CatchUpAction::Deliver,
+        CatchUpPolicy::Show => CatchUpAction::ShowOnly,
+        CatchUpPolicy::Ignore => {
+            if now - received_at > max_age as i64 {
+                CatchUpAction::Skip
+            } else {
+                CatchUpAction::ShowOnly
+            }
+        }
+    }
+}
+
+// 记录启动时间，并在后台检测系统唤醒，唤醒后刷新 ACTIVATED_AT
+pub fn wake_monitor_thread() {
+    mark_activated(unix_now());
+    thread::spawn(move || {
+        let mut last_tick = unix_now();
+        loop {
+            sleep(Duration::from_secs(1));
+            let now = unix_now();
+            if now - last_tick > WAKE_GAP_SECS {
+                info!("{}", t!("system-resumed"));
+                mark_activated(now);
+            }
+            last_tick = now;
+        }
+    });
+}
@@ -0,0 +1,211 @@
+use std::{
+    error::Error,
+    thread,
+    thread::sleep,
+    time::{Duration, Instant},
+};
+
+use arboard::Clipboard;
+use log::{info, warn};
+use rust_i18n::t;
+
+use crate::{
+    catch_up::unix_now,
+    clipboard_snapshot::{self, ClipboardSnapshot, SnapshotItem},
+    get_old_clipboard_contents, recover_clipboard_contents, MAConfig,
+};
+
+// 剪贴板管理器约定的提示：macOS 上 nspasteboard.org 的类型，Linux 上 KDE Klipper 的 MIME 类型，
+// 多数剪贴板历史工具看到它们时不记录内容
+const CONCEALED_TYPE: &str = "org.nspasteboard.ConcealedType";
+const TRANSIENT_TYPE: &str = "org.nspasteboard.TransientType";
+const KDE_PASSWORD_MANAGER_HINT: &str = "x-kde-passwordManagerHint";
+
+// 带有剪贴板历史排除提示的纯文本
+pub fn concealed_text(text: &str) -> ClipboardSnapshot {
+    let mut formats = Vec::new();
+    if cfg!(target_os = "macos") {
+        formats.push((
+            "public.utf8-plain-text".to_string(),
+            text.as_bytes().to_vec(),
+        ));
+        formats.push((CONCEALED_TYPE.to_string(), Vec::new()));
+        formats.push((TRANSIENT_TYPE.to_string(), Vec::new()));
+    } else {
+        for name in ["UTF8_STRING", "text/plain;charset=utf-8", "text/plain"] {
+            formats.push((name.to_string(), text.as_bytes().to_vec()));
+        }
+        if text.is_ascii() {
+            formats.push(("STRING".to_string(), text.as_bytes().to_vec()));
+        }
+        formats.push((KDE_PASSWORD_MANAGER_HINT.to_string(), b"secret".to_vec()));
+    }
+    ClipboardSnapshot {
+        items: vec![SnapshotItem { formats }],
+    }
+}
+
+// 所有写剪贴板的地方都经过这里，写入的内容不会进入剪贴板历史；
+// 无法直接写入平台剪贴板时退回 arboard 写入纯文本
+pub fn write_clipboard(text: &str) -> Result<(), Box<dyn Error>> {
+    if let Err(e) = clipboard_snapshot::restore(&concealed_text(text)) {
+        warn!("{}: {:?}", t!("error-conceal-clipboard"), e);
+        Clipboard::new()?.set_text(text)?;
+    }
+    Ok(())
+}
+
+// 写入验证码前保存的剪贴板内容
+pub enum ClipboardContents {
+    // 所有条目的所有格式
+    Snapshot(ClipboardSnapshot),
+    // 无法读取所有格式时只保存文本或图片
+    Basic(
+        Result<String, arboard::Error>,
+        Result<arboard::ImageData<'static>, arboard::Error>,
+    ),
+}
+
+// 保存剪贴板的所有格式，失败时退回只保存文本或图片
+pub fn snapshot_clipboard(limit: usize) -> ClipboardContents {
+    match clipboard_snapshot::capture(limit) {
+        Ok(snapshot) => ClipboardContents::Snapshot(snapshot),
+        Err(e) => {
+            warn!("{}: {:?}", t!("error-snapshot-clipboard"), e);
+            let (text, image) = get_old_clipboard_contents();
+            ClipboardContents::Basic(text, image)
+        }
+    }
+}
+
+pub fn restore_clipboard(contents: ClipboardContents) {
+    match contents {
+        ClipboardContents::Snapshot(snapshot) => match clipboard_snapshot::restore(&snapshot) {
+            Ok(_) => info!("{}:{:?}", t!("old-clpb-contents"), snapshot.format_names()),
+            Err(e) => warn!("{}: {:?}", t!("unable-to-recover-clipboard"), e),
+        },
+        ClipboardContents::Basic(text, image) => recover_clipboard_contents((text, image)),
+    }
+}
+
+pub fn default_restore_clipboard_delay() -> u64 {
+    2000
+}
+
+// 按 clear_clipboard_after 和 clear_clipboard_at_expiry 计算清空剪贴板的时间（unix 秒），
+// 两者都适用时取较早的一个；信息中没有写明有效期时只按 clear_clipboard_after
+pub fn clear_deadline(
+    config: &MAConfig,
+    delivered_at: i64,
+    expires_at: Option<i64>,
+) -> Option<i64> {
+    let after = config
+        .clear_clipboard_after
+        .map(|seconds| delivered_at + seconds as i64);
+    let expiry = expires_at.filter(|_| config.clear_clipboard_at_expiry);
+    match (after, expiry) {
+        (Some(after), Some(expiry)) => Some(after.min(expiry)),
+        (after, expiry) => after.or(expiry),
+    }
+}
+
+// 剪贴板中是否仍是验证码，无法读取时视为否
+pub fn clipboard_holds(code: &str) -> bool {
+    Clipboard::new()
+        .and_then(|mut clipboard| clipboard.get_text())
+        .is_ok_and(|text| text == code)
+}
+
+// 写入验证码后的剪贴板，之后用来判断剪贴板是否被改写
+#[derive(Debug, Clone)]
+pub struct ClipboardMark {
+    code: String,
+    token: Option<u64>,
+}
+
+impl ClipboardMark {
+    // 写入验证码后立即记录
+    pub fn new(code: &str) -> Self {
+        ClipboardMark {
+            code: code.to_string(),
+            token: clipboard_snapshot::change_token(),
+        }
+    }
+
+    // 有变化标识时按标识判断，用户再次复制相同的文本也算改写；没有时退回比较内容
+    pub fn still_held(&self) -> bool {
+        match self.token {
+            Some(token) => clipboard_snapshot::change_token() == Some(token),
+            None => clipboard_holds(&self.code),
+        }
+    }
+}
+
+// 从 since（粘贴完成或写入剪贴板的时间）起等待 delay，让目标应用读完剪贴板后再恢复原内容；
+// 等待期间剪贴板被改写说明用户复制了新内容，放弃恢复。返回是否已恢复
+pub fn restore_if_owned(
+    mark: &ClipboardMark,
+    contents: ClipboardContents,
+    since: Instant,
+    delay: Duration,
+) -> bool {
+    let deadline = since + delay;
+    loop {
+        if !mark.still_held() {
+            info!("{}", t!("clipboard-changed-not-restored"));
+            return false;
+        }
+        let now = Instant::now();
+        if now >= deadline {
+            break;
+        }
+        sleep((deadline - now).min(Duration::from_millis(100)));
+    }
+    restore_clipboard(contents);
+    true
+}
+
+// 剪贴板中仍是验证码时才清空，用户之后复制的内容不受影响；返回是否已清空
+pub fn clear_if_owned(code: &str) -> Result<bool, Box<dyn Error>> {
+    let mut clipboard = Clipboard::new()?;
+    match clipboard.get_text() {
+        Ok(text) if text == code => {
+            clipboard.clear()?;
+            Ok(true)
+        }
+        _ => Ok(false),
+    }
+}
+
+// 等到 deadline 后清空剪贴板，阻塞当前线程
+pub fn clear_at(code: &str, deadline: i64) {
+    let wait = deadline - unix_now();
+    if wait > 0 {
+        sleep(Duration::from_secs(wait as u64));
+    }
+    match clear_if_owned(code) {
+        Ok(true) => info!("{}", t!("clipboard-cleared")),
+        Ok(false) => info!("{}", t!("clipboard-changed-not-cleared")),
+        Err(e) => warn!("{}: {:?}", t!("error-clear-clipboard"), e),
+    }
+}
+
+// 在后台线程中等待并恢复剪贴板原内容，不阻塞信息源
+pub fn schedule_restore(
+    mark: ClipboardMark,
+    contents: ClipboardContents,
+    since: Instant,
+    delay: Duration,
+) {
+    let _ = thread::Builder::new()
+        .name("clipboard-restore".to_string())
+        .spawn(move || {
+            restore_if_owned(&mark, contents, since, delay);
+        });
+}
+
+pub fn schedule_clear(code: String, deadline: i64) {
+    let _ = thread::Builder::new()
+        .name("clipboard-clear".to_string())
+        .spawn(move || clear_at(&code, deadline));
+}
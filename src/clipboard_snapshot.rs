@@ -0,0 +1,540 @@
+use std::{error::Error, time::Instant};
+
+use arboard::Clipboard;
+use log::warn;
+use rust_i18n::t;
+
+// 剪贴板中一个条目的所有格式：(格式名, 数据)。格式名在 macOS 上是 UTI，
+// 在 Linux 上是 X11 目标名（通常是 MIME 类型）
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SnapshotItem {
+    pub formats: Vec<(String, Vec<u8>)>,
+}
+
+// macOS 剪贴板可以有多个条目（例如复制了多个文件），Linux 只有一个
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ClipboardSnapshot {
+    pub items: Vec<SnapshotItem>,
+}
+
+impl ClipboardSnapshot {
+    pub fn is_empty(&self) -> bool {
+        self.items.iter().all(|item| item.formats.is_empty())
+    }
+
+    pub fn format_names(&self) -> Vec<&str> {
+        self.items
+            .iter()
+            .flat_map(|item| item.formats.iter().map(|(name, _)| name.as_str()))
+            .collect()
+    }
+}
+
+pub fn default_clipboard_snapshot_limit() -> usize {
+    32 * 1024 * 1024
+}
+
+// 快照的总大小上限，超出上限的格式不保存
+pub struct SnapshotBudget {
+    remaining: usize,
+}
+
+impl SnapshotBudget {
+    pub fn new(limit: usize) -> Self {
+        SnapshotBudget { remaining: limit }
+    }
+
+    pub fn admit(&mut self, name: &str, len: usize) -> bool {
+        if len > self.remaining {
+            warn!(
+                "{}: {} ({} bytes)",
+                t!("clipboard-format-too-large"),
+                name,
+                len
+            );
+            return false;
+        }
+        self.remaining -= len;
+        true
+    }
+}
+
+// macOS 上不保存的类型：文件承诺（读取时才由来源应用生成文件内容）、系统按需转换出的动态类型
+// 和旧式 Carbon 类型。读取这些类型会让来源应用或系统先生成完整数据，而恢复原始类型后它们会被重新提供
+const SKIPPED_PASTEBOARD_TYPES: &[&str] = &[
+    "com.apple.pasteboard.promised-",
+    "com.apple.NSFilePromise",
+    "NSPromiseContentsPboardType",
+    "dyn.",
+    "CorePasteboardFlavorType",
+];
+
+pub fn should_capture_pasteboard_type(name: &str) -> bool {
+    !SKIPPED_PASTEBOARD_TYPES
+        .iter()
+        .any(|prefix| name.starts_with(prefix))
+}
+
+// X11 上超过单个请求上限的数据要用 INCR 分段发送，恢复剪贴板时不支持，这样的格式既不保存也不提供
+pub fn fits_single_request(name: &str, len: usize, maximum_request_bytes: usize) -> bool {
+    // 请求头和 ChangeProperty 的固定字段
+    if len + 64 < maximum_request_bytes {
+        return true;
+    }
+    warn!(
+        "{}: {} ({} bytes)",
+        t!("clipboard-format-exceeds-request"),
+        name,
+        len
+    );
+    false
+}
+
+// 保存剪贴板中所有条目的所有格式，总大小不超过 limit 字节
+#[cfg(any(target_os = "macos", target_os = "linux"))]
+pub fn capture(limit: usize) -> Result<ClipboardSnapshot, Box<dyn Error>> {
+    let mut budget = SnapshotBudget::new(limit);
+    #[cfg(target_os = "macos")]
+    let items = pasteboard::capture(&mut budget)?;
+    #[cfg(target_os = "linux")]
+    let items = x11::capture(&mut budget)?;
+    Ok(ClipboardSnapshot { items })
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "linux")))]
+pub fn capture(_limit: usize) -> Result<ClipboardSnapshot, Box<dyn Error>> {
+    Err("unsupported platform".into())
+}
+
+// 用快照替换剪贴板内容，快照为空时清空剪贴板
+pub fn restore(snapshot: &ClipboardSnapshot) -> Result<(), Box<dyn Error>> {
+    if snapshot.is_empty() {
+        Clipboard::new()?.clear()?;
+        return Ok(());
+    }
+    #[cfg(target_os = "macos")]
+    return pasteboard::restore(&snapshot.items);
+    #[cfg(target_os = "linux")]
+    return x11::restore(snapshot.items[0].clone());
+    #[cfg(not(any(target_os = "macos", target_os = "linux")))]
+    Err("unsupported platform".into())
+}
+
+// 剪贴板的变化标识：macOS 上是 changeCount，Linux 上是本进程取得 CLIPBOARD 所有权的序号，
+// 其他程序持有剪贴板时为空。标识变了说明剪贴板被改写过，即使写入的内容相同
+pub fn change_token() -> Option<u64> {
+    #[cfg(target_os = "macos")]
+    return pasteboard::change_count();
+    #[cfg(target_os = "linux")]
+    return x11::owned_generation();
+    #[cfg(not(any(target_os = "macos", target_os = "linux")))]
+    None
+}
+
+// 等到本进程不再持有剪贴板，也就是其他程序写入了剪贴板，最多等到 deadline。X11 剪贴板的内容由
+// 持有者进程提供，浮动窗口这样的短生命周期进程退出前需要调用，否则写入或恢复的内容会随进程一起消失
+pub fn wait_until_released(deadline: Instant) {
+    #[cfg(target_os = "linux")]
+    x11::wait_until_released(deadline);
+    #[cfg(not(target_os = "linux"))]
+    let _ = deadline;
+}
+
+#[cfg(target_os = "macos")]
+mod pasteboard {
+    use std::error::Error;
+
+    use log::warn;
+    use objc2::{rc::Retained, runtime::ProtocolObject};
+    use objc2_app_kit::{NSPasteboard, NSPasteboardItem, NSPasteboardWriting};
+    use objc2_foundation::{NSArray, NSData, NSString};
+    use rust_i18n::t;
+
+    use super::{should_capture_pasteboard_type, SnapshotBudget, SnapshotItem};
+
+    // NSPasteboardItem 无法在读取前得知数据大小，只能先按类型过滤，额度用完后不再读取
+    pub fn capture(budget: &mut SnapshotBudget) -> Result<Vec<SnapshotItem>, Box<dyn Error>> {
+        let mut items = Vec::new();
+        unsafe {
+            let pasteboard = NSPasteboard::generalPasteboard();
+            let Some(pasteboard_items) = pasteboard.pasteboardItems() else {
+                return Ok(items);
+            };
+            for item in pasteboard_items.iter() {
+                let mut formats = Vec::new();
+                for kind in item.types().iter() {
+                    let name = kind.to_string();
+                    if !should_capture_pasteboard_type(&name) {
+                        continue;
+                    }
+                    if budget.remaining == 0 {
+                        warn!("{}: {}", t!("clipboard-format-too-large"), name);
+                        continue;
+                    }
+                    if let Some(data) = item.dataForType(kind) {
+                        if budget.admit(&name, data.len()) {
+                            formats.push((name, data.bytes().to_vec()));
+                        }
+                    }
+                }
+                items.push(SnapshotItem { formats });
+            }
+        }
+        Ok(items)
+    }
+
+    pub fn change_count() -> Option<u64> {
+        Some(unsafe { NSPasteboard::generalPasteboard().changeCount() } as u64)
+    }
+
+    pub fn restore(items: &[SnapshotItem]) -> Result<(), Box<dyn Error>> {
+        unsafe {
+            let objects: Vec<Retained<ProtocolObject<dyn NSPasteboardWriting>>> = items
+                .iter()
+                .map(|item| {
+                    let pasteboard_item = NSPasteboardItem::new();
+                    for (name, data) in &item.formats {
+                        pasteboard_item
+                            .setData_forType(&NSData::with_bytes(data), &NSString::from_str(name));
+                    }
+                    ProtocolObject::from_retained(pasteboard_item)
+                })
+                .collect();
+            let pasteboard = NSPasteboard::generalPasteboard();
+            pasteboard.clearContents();
+            if !pasteboard.writeObjects(&NSArray::from_vec(objects)) {
+                return Err("failed to write pasteboard".into());
+            }
+        }
+        Ok(())
+    }
+}
+
+// 通过 X11 CLIPBOARD 选区读写；Wayland 会话中经 XWayland 与 Wayland 剪贴板同步
+#[cfg(target_os = "linux")]
+mod x11 {
+    use std::{
+        error::Error,
+        sync::{
+            atomic::{AtomicU64, Ordering},
+            mpsc, Mutex,
+        },
+        thread::{self, sleep, JoinHandle},
+        time::{Duration, Instant},
+    };
+
+    use log::{info, warn};
+    use rust_i18n::t;
+    use x11rb::{
+        connection::{Connection, RequestConnection},
+        protocol::{
+            xproto::{
+                Atom, AtomEnum, ConnectionExt, CreateWindowAux, EventMask, PropMode, Property,
+                SelectionNotifyEvent, SelectionRequestEvent, Window, WindowClass,
+                SELECTION_NOTIFY_EVENT,
+            },
+            Event,
+        },
+        rust_connection::RustConnection,
+        wrapper::ConnectionExt as _,
+        COPY_DEPTH_FROM_PARENT, CURRENT_TIME, NONE,
+    };
+
+    use super::{fits_single_request, SnapshotBudget, SnapshotItem};
+
+    // 等待剪贴板所有者响应的时间，分段传输时每收到一段重新计时
+    const READ_TIMEOUT: Duration = Duration::from_secs(1);
+    // 等待其他程序写入剪贴板时检查持有线程的间隔
+    const RELEASE_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+    // 描述选区本身而不是内容的目标，不保存
+    const META_TARGETS: &[&str] = &[
+        "TARGETS",
+        "MULTIPLE",
+        "TIMESTAMP",
+        "SAVE_TARGETS",
+        "DELETE",
+        "INSERT_SELECTION",
+        "INSERT_PROPERTY",
+    ];
+
+    // 属性的类型、位宽和数据
+    type PropertyValue = (Atom, u8, Vec<u8>);
+
+    // 当前持有剪贴板的线程，失去所有权时退出
+    static OWNER: Mutex<Option<JoinHandle<()>>> = Mutex::new(None);
+    // 每次取得所有权的序号；本进程持有剪贴板时 OWNED_GENERATION 是当前的序号，否则为 0
+    static NEXT_GENERATION: AtomicU64 = AtomicU64::new(1);
+    static OWNED_GENERATION: AtomicU64 = AtomicU64::new(0);
+
+    struct Atoms {
+        clipboard: Atom,
+        targets: Atom,
+        incr: Atom,
+        property: Atom,
+    }
+
+    fn intern(conn: &RustConnection, name: &str) -> Result<Atom, Box<dyn Error>> {
+        Ok(conn.intern_atom(false, name.as_bytes())?.reply()?.atom)
+    }
+
+    fn connect() -> Result<(RustConnection, Window, Atoms), Box<dyn Error>> {
+        let (conn, screen_num) = RustConnection::connect(None)?;
+        let root = conn.setup().roots[screen_num].root;
+        let window = conn.generate_id()?;
+        conn.create_window(
+            COPY_DEPTH_FROM_PARENT,
+            window,
+            root,
+            0,
+            0,
+            1,
+            1,
+            0,
+            WindowClass::INPUT_ONLY,
+            0,
+            &CreateWindowAux::new().event_mask(EventMask::PROPERTY_CHANGE),
+        )?;
+        let atoms = Atoms {
+            clipboard: intern(&conn, "CLIPBOARD")?,
+            targets: intern(&conn, "TARGETS")?,
+            incr: intern(&conn, "INCR")?,
+            property: intern(&conn, "MESSAUTO_SNAPSHOT")?,
+        };
+        conn.flush()?;
+        Ok((conn, window, atoms))
+    }
+
+    fn atom_name(conn: &RustConnection, atom: Atom) -> Result<String, Box<dyn Error>> {
+        Ok(String::from_utf8_lossy(&conn.get_atom_name(atom)?.reply()?.name).into_owned())
+    }
+
+    // 请求把选区转换为 target，返回读到的属性；所有者拒绝时为空。
+    // 数据较大时所有者使用 INCR 分段传输，累计超过 max_len 时放弃
+    fn read_target(
+        conn: &RustConnection,
+        window: Window,
+        atoms: &Atoms,
+        target: Atom,
+        max_len: usize,
+    ) -> Result<Option<PropertyValue>, Box<dyn Error>> {
+        conn.delete_property(window, atoms.property)?;
+        conn.convert_selection(
+            window,
+            atoms.clipboard,
+            target,
+            atoms.property,
+            CURRENT_TIME,
+        )?;
+        conn.flush()?;
+        let mut deadline = Instant::now() + READ_TIMEOUT;
+        let mut incr: Option<PropertyValue> = None;
+        while Instant::now() < deadline {
+            let Some(event) = conn.poll_for_event()? else {
+                sleep(Duration::from_millis(1));
+                continue;
+            };
+            match event {
+                Event::SelectionNotify(event) if incr.is_none() => {
+                    if event.property == NONE {
+                        return Ok(None);
+                    }
+                    let reply = conn
+                        .get_property(true, window, atoms.property, AtomEnum::ANY, 0, u32::MAX / 4)?
+                        .reply()?;
+                    if reply.type_ != atoms.incr {
+                        return Ok(Some((reply.type_, reply.format, reply.value)));
+                    }
+                    incr = Some((NONE, 8, Vec::new()));
+                    deadline = Instant::now() + READ_TIMEOUT;
+                }
+                Event::PropertyNotify(event)
+                    if event.atom == atoms.property && event.state == Property::NEW_VALUE =>
+                {
+                    let Some((kind, format, data)) = incr.as_mut() else {
+                        continue;
+                    };
+                    let reply = conn
+                        .get_property(true, window, atoms.property, AtomEnum::ANY, 0, u32::MAX / 4)?
+                        .reply()?;
+                    if reply.value.is_empty() {
+                        return Ok(incr);
+                    }
+                    if data.len() + reply.value.len() > max_len {
+                        return Err("selection exceeds snapshot limit".into());
+                    }
+                    (*kind, *format) = (reply.type_, reply.format);
+                    data.extend(reply.value);
+                    deadline = Instant::now() + READ_TIMEOUT;
+                }
+                _ => {}
+            }
+        }
+        Err("timed out reading selection".into())
+    }
+
+    pub fn capture(budget: &mut SnapshotBudget) -> Result<Vec<SnapshotItem>, Box<dyn Error>> {
+        let (conn, window, atoms) = connect()?;
+        if conn.get_selection_owner(atoms.clipboard)?.reply()?.owner == NONE {
+            return Ok(Vec::new());
+        }
+        let Some((_, 32, targets)) = read_target(&conn, window, &atoms, atoms.targets, 1 << 16)?
+        else {
+            return Err("selection owner did not report targets".into());
+        };
+        let mut formats = Vec::new();
+        for target in targets
+            .chunks_exact(4)
+            .map(|atom| u32::from_ne_bytes(atom.try_into().unwrap()))
+        {
+            let name = atom_name(&conn, target)?;
+            if META_TARGETS.contains(&name.as_str()) {
+                continue;
+            }
+            // 只保存 8 位数据，32 位属性是原子列表或整数等元数据
+            match read_target(&conn, window, &atoms, target, budget.remaining) {
+                Ok(Some((kind, 8, data))) if kind == target => {
+                    if fits_single_request(&name, data.len(), conn.maximum_request_bytes())
+                        && budget.admit(&name, data.len())
+                    {
+                        formats.push((name, data));
+                    }
+                }
+                Ok(_) => {}
+                Err(e) => warn!("{}: {}: {}", t!("error-snapshot-clipboard"), name, e),
+            }
+        }
+        Ok(vec![SnapshotItem { formats }])
+    }
+
+    fn respond(
+        conn: &RustConnection,
+        atoms: &Atoms,
+        formats: &[(Atom, Vec<u8>)],
+        request: SelectionRequestEvent,
+    ) -> Result<(), Box<dyn Error>> {
+        // 旧客户端不指定属性时使用目标名
+        let property = if request.property == NONE {
+            request.target
+        } else {
+            request.property
+        };
+        let served = if request.target == atoms.targets {
+            let mut targets: Vec<Atom> = formats.iter().map(|(atom, _)| *atom).collect();
+            targets.push(atoms.targets);
+            conn.change_property32(
+                PropMode::REPLACE,
+                request.requestor,
+                property,
+                AtomEnum::ATOM,
+                &targets,
+            )?;
+            true
+        } else {
+            match formats.iter().find(|(atom, _)| *atom == request.target) {
+                Some((atom, data)) => {
+                    conn.change_property8(
+                        PropMode::REPLACE,
+                        request.requestor,
+                        property,
+                        *atom,
+                        data,
+                    )?;
+                    true
+                }
+                _ => false,
+            }
+        };
+        conn.send_event(
+            false,
+            request.requestor,
+            EventMask::NO_EVENT,
+            SelectionNotifyEvent {
+                response_type: SELECTION_NOTIFY_EVENT,
+                sequence: request.sequence,
+                time: request.time,
+                requestor: request.requestor,
+                selection: request.selection,
+                target: request.target,
+                property: if served { property } else { NONE },
+            },
+        )?;
+        conn.flush()?;
+        Ok(())
+    }
+
+    // 在后台线程中成为 CLIPBOARD 的所有者并响应读取请求，直到其他程序写入剪贴板
+    pub fn restore(item: SnapshotItem) -> Result<(), Box<dyn Error>> {
+        let (owned_tx, owned_rx) = mpsc::channel();
+        let owner = thread::Builder::new()
+            .name("clipboard-owner".to_string())
+            .spawn(move || {
+                let generation = NEXT_GENERATION.fetch_add(1, Ordering::SeqCst);
+                let serve = || -> Result<(), Box<dyn Error>> {
+                    let (conn, window, atoms) = connect()?;
+                    // 不在 TARGETS 中列出无法发送的格式
+                    let formats = item
+                        .formats
+                        .into_iter()
+                        .filter(|(name, data)| {
+                            fits_single_request(name, data.len(), conn.maximum_request_bytes())
+                        })
+                        .map(|(name, data)| Ok((intern(&conn, &name)?, data)))
+                        .collect::<Result<Vec<_>, Box<dyn Error>>>()?;
+                    conn.set_selection_owner(window, atoms.clipboard, CURRENT_TIME)?;
+                    if conn.get_selection_owner(atoms.clipboard)?.reply()?.owner != window {
+                        return Err("failed to take clipboard ownership".into());
+                    }
+                    OWNED_GENERATION.store(generation, Ordering::SeqCst);
+                    let _ = owned_tx.send(Ok(()));
+                    loop {
+                        match conn.wait_for_event()? {
+                            Event::SelectionRequest(request) => {
+                                respond(&conn, &atoms, &formats, request)?
+                            }
+                            Event::SelectionClear(_) => return Ok(()),
+                            _ => {}
+                        }
+                    }
+                };
+                if let Err(e) = serve() {
+                    let _ = owned_tx.send(Err(e.to_string()));
+                }
+                // 之后的线程可能已取得所有权，只清除自己的序号
+                let _ = OWNED_GENERATION.compare_exchange(
+                    generation,
+                    0,
+                    Ordering::SeqCst,
+                    Ordering::SeqCst,
+                );
+            })?;
+        owned_rx
+            .recv_timeout(READ_TIMEOUT)
+            .map_err(|_| "timed out taking clipboard ownership")??;
+        // 之前的线程在新线程取得所有权时收到 SelectionClear 后退出
+        *OWNER.lock().unwrap() = Some(owner);
+        Ok(())
+    }
+
+    pub fn owned_generation() -> Option<u64> {
+        Some(OWNED_GENERATION.load(Ordering::SeqCst)).filter(|generation| *generation != 0)
+    }
+
+    pub fn wait_until_released(deadline: Instant) {
+        // 等待期间可能又恢复了一次剪贴板，直到没有持有剪贴板的线程为止
+        loop {
+            let owner = OWNER.lock().unwrap().take();
+            let Some(owner) = owner else {
+                return;
+            };
+            while !owner.is_finished() {
+                if Instant::now() >= deadline {
+                    info!("{}", t!("clipboard-hold-timeout"));
+                    return;
+                }
+                sleep(RELEASE_POLL_INTERVAL);
+            }
+            let _ = owner.join();
+        }
+    }
+}
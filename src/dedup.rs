@@ -0,0 +1,97 @@
+use std::sync::Mutex;
+
+use log::debug;
+use rust_i18n::t;
+
+pub fn default_dedup_window() -> u64 {
+    60
+}
+
+// 最近一次投递的验证码记录
+#[derive(Debug, Clone)]
+struct DeliveredCode {
+    code: String,
+    sender: Option<String>,
+    message_id: Option<String>,
+    // 投递时间（unix 秒）
+    at: i64,
+}
+
+// 跨信息源的验证码去重：同一验证码经短信、iMessage 与邮件先后到达时只投递一次
+#[derive(Debug, Default)]
+pub struct Deduplicator {
+    delivered: Vec<DeliveredCode>,
+}
+
+impl Deduplicator {
+    pub const fn new() -> Self {
+        Deduplicator {
+            delivered: Vec::new(),
+        }
+    }
+
+    // 判断是否为窗口期内的重复验证码；不重复时记录下来并返回 false
+    // match_sender 为 true 时，只有发件人也相同才视为重复
+    pub fn check_and_record(
+        &mut self,
+        code: &str,
+        sender: Option<&str>,
+        message_id: Option<&str>,
+        window: u64,
+        match_sender: bool,
+        now: i64,
+    ) -> bool {
+        self.delivered.retain(|d| now - d.at <= window as i64);
+
+        let known_id = message_id.is_some()
+            && self
+                .delivered
+                .iter()
+                .any(|d| d.message_id.as_deref() == message_id);
+        let duplicate = known_id
+            || self
+                .delivered
+                .iter()
+                .any(|d| d.code == code && (!match_sender || d.sender.as_deref() == sender));
+        // 被抑制的邮件也记下 Message-ID，避免同一封邮件稍后再次触发
+        if !duplicate || (message_id.is_some() && !known_id) {
+            self.delivered.push(DeliveredCode {
+                code: code.to_string(),
+                sender: sender.map(str::to_string),
+                message_id: message_id.map(str::to_string),
+                at: now,
+            });
+        }
+        duplicate
+    }
+}
+
+static DEDUPLICATOR: Mutex<Deduplicator> = Mutex::new(Deduplicator::new());
+
+// 全局去重入口，所有信息源在投递前调用；window 为 0 时不去重
+pub fn is_duplicate_code(
+    code: &str,
+    sender: Option<&str>,
+    message_id: Option<&str>,
+    window: u64,
+    match_sender: bool,
+    now: i64,
+) -> bool {
+    if window == 0 {
+        return false;
+    }
+    let duplicate = DEDUPLICATOR
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .check_and_record(code, sender, message_id, window, match_sender, now);
+    if duplicate {
+        debug!(
+            "{}: {:?} {:?} {:?}",
+            t!("duplicate-code-suppressed"),
+            code,
+            sender,
+            message_id
+        );
+    }
+    duplicate
+}
@@ -0,0 +1,435 @@
+use std::{
+    error::Error,
+    io::Write,
+    process::{Command, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+use log::{error, info};
+use rust_i18n::t;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::{
+    catch_up::unix_now,
+    clipboard::{
+        clear_deadline, schedule_clear, schedule_restore, snapshot_clipboard, write_clipboard,
+        ClipboardContents, ClipboardMark,
+    },
+    desktop_notification::NotificationSink,
+    frontmost::{FrontmostApp, FrontmostAppProvider, SystemFrontmostApp},
+    get_captcha_lifetime,
+    hook::{CommandSink, HookConfig},
+    jmap::curl_quote,
+    key_sequence::{key_sequence_for, parse_key_sequence, run_key_sequence},
+    keystroke::{default_key_delay, keyboard_backend, KeyboardBackendKind, KeystrokeSink},
+    open_app, paste_script, return_script,
+    source::IncomingMessage,
+    webhook::sign,
+    MAConfig,
+};
+
+// 按类别配置投递方式时使用的键，"default" 匹配所有信息
+pub const MAIL_CATEGORY: &str = "mail";
+pub const SMS_CATEGORY: &str = "sms";
+pub const DEFAULT_CATEGORY: &str = "default";
+
+// 配置文件中的一种投递方式，例如 {"type": "paste"}
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SinkConfig {
+    // 复制到剪贴板
+    Clipboard,
+    // 复制到剪贴板并粘贴，auto_return 开启时再按回车
+    Paste,
+    // 在浮动窗口中显示
+    FloatWindow,
+    // 以 JSON POST 到指定地址，设置 secret 时附带 HMAC 签名
+    Webhook {
+        url: String,
+        #[serde(default)]
+        secret: Option<String>,
+    },
+    // 运行外部命令，验证码通过环境变量和标准输入传入
+    Command(HookConfig),
+    // 显示带“复制”按钮的系统通知
+    Notification,
+    // 模拟键盘逐字符输入，不占用剪贴板，auto_return 开启时再按回车
+    Keystroke {
+        #[serde(default)]
+        backend: KeyboardBackendKind,
+        #[serde(default = "default_key_delay")]
+        key_delay: u64,
+    },
+}
+
+impl SinkConfig {
+    pub fn build(&self) -> Box<dyn DeliverySink> {
+        match self {
+            SinkConfig::Clipboard => Box::new(ClipboardSink),
+            SinkConfig::Paste => Box::new(PasteSink {
+                apps: Box::new(SystemFrontmostApp),
+            }),
+            SinkConfig::FloatWindow => Box::new(FloatWindowSink),
+            SinkConfig::Webhook { url, secret } => Box::new(WebhookSink {
+                url: url.clone(),
+                secret: secret.clone(),
+            }),
+            SinkConfig::Command(hook) => Box::new(CommandSink(hook.clone())),
+            SinkConfig::Notification => Box::new(NotificationSink),
+            SinkConfig::Keystroke { backend, key_delay } => Box::new(KeystrokeSink {
+                backend: *backend,
+                key_delay: *key_delay,
+                apps: Box::new(SystemFrontmostApp),
+            }),
+        }
+    }
+
+    // 直接把验证码交给前台应用或剪贴板的投递方式
+    pub fn is_interactive(&self) -> bool {
+        matches!(
+            self,
+            SinkConfig::Clipboard | SinkConfig::Paste | SinkConfig::Keystroke { .. }
+        )
+    }
+}
+
+pub fn category(message: &IncomingMessage) -> &'static str {
+    if message.is_mail() {
+        MAIL_CATEGORY
+    } else {
+        SMS_CATEGORY
+    }
+}
+
+// 依次按信息源名称（如 "imap:Work"）、信息源类型（如 "imap"）、类别（"mail"/"sms"）
+// 和 "default" 查找投递方式；都没有配置时按 auto_paste 开关决定
+pub fn sinks_for(message: &IncomingMessage, config: &MAConfig) -> Vec<SinkConfig> {
+    let kind = message.source.split(':').next().unwrap_or_default();
+    let configured = [
+        message.source.as_str(),
+        kind,
+        category(message),
+        DEFAULT_CATEGORY,
+    ]
+    .iter()
+    .find_map(|key| config.delivery.get(*key));
+    if let Some(sinks) = configured {
+        return sinks.clone();
+    }
+    if config.auto_paste {
+        vec![SinkConfig::Paste]
+    } else {
+        vec![SinkConfig::Clipboard]
+    }
+}
+
+// 只显示验证码时把交互式的投递方式换成浮动窗口，webhook、命令和通知照常执行
+pub fn show_only(sinks: Vec<SinkConfig>) -> Vec<SinkConfig> {
+    let mut shown = Vec::new();
+    for sink in sinks {
+        let sink = if sink.is_interactive() {
+            SinkConfig::FloatWindow
+        } else {
+            sink
+        };
+        if !shown.contains(&sink) {
+            shown.push(sink);
+        }
+    }
+    shown
+}
+
+// 一次投递在各个投递方式之间共享的状态
+pub struct DeliveryContext<'a> {
+    pub code: &'a str,
+    pub message: &'a IncomingMessage,
+    pub config: &'a MAConfig,
+    // 验证码过期时间（unix 秒），信息中没有写明有效期时为空
+    pub expires_at: Option<i64>,
+    // 最近一次写入剪贴板或粘贴的时间，恢复剪贴板原内容时从这里开始等待
+    clipboard_used_at: Option<Instant>,
+    snapshot: Option<ClipboardContents>,
+    // 写入验证码后的剪贴板，恢复原内容前据此判断剪贴板是否被改写
+    mark: Option<ClipboardMark>,
+    // 验证码已写入剪贴板或输入到前台应用
+    handed_over: bool,
+}
+
+impl<'a> DeliveryContext<'a> {
+    pub fn new(code: &'a str, message: &'a IncomingMessage, config: &'a MAConfig) -> Self {
+        DeliveryContext {
+            code,
+            message,
+            config,
+            expires_at: get_captcha_lifetime(&message.body)
+                .map(|lifetime| message.received_at + lifetime as i64),
+            clipboard_used_at: None,
+            snapshot: None,
+            mark: None,
+            handed_over: false,
+        }
+    }
+
+    // 同一次投递只写一次剪贴板，首次写入前按 recover_clipboard 保存原内容
+    pub fn set_clipboard(&mut self) -> Result<(), Box<dyn Error>> {
+        if self.clipboard_set() {
+            return Ok(());
+        }
+        if self.config.recover_clipboard {
+            self.snapshot = Some(snapshot_clipboard(self.config.clipboard_snapshot_limit));
+        }
+        write_clipboard(self.code)?;
+        self.mark = Some(ClipboardMark::new(self.code));
+        self.clipboard_used_at = Some(Instant::now());
+        self.handed_over = true;
+        Ok(())
+    }
+
+    // 模拟键盘输入验证码后调用；只显示浮动窗口或通知、交给外部命令都不算
+    pub fn mark_handed_over(&mut self) {
+        self.handed_over = true;
+    }
+
+    pub fn clipboard_set(&self) -> bool {
+        self.clipboard_used_at.is_some()
+    }
+
+    // 粘贴按键已发出，目标应用此后才会读取剪贴板
+    pub fn mark_pasted(&mut self) {
+        self.clipboard_used_at = Some(Instant::now());
+    }
+
+    // 交给 webhook、命令等外部集成的验证码信息
+    pub fn payload(&self) -> Value {
+        json!({
+            "code": self.code,
+            "sender": self.message.sender,
+            "source": self.message.source,
+            "category": category(self.message),
+            "received_at": self.message.received_at,
+            "expires_at": self.expires_at,
+        })
+    }
+
+    // 所有投递方式完成后按配置安排清空剪贴板，并恢复剪贴板原内容
+    fn finish(self) {
+        if self.clipboard_set() {
+            if let Some(deadline) = clear_deadline(self.config, unix_now(), self.expires_at) {
+                schedule_clear(self.code.to_string(), deadline);
+            }
+        }
+        if let (Some(contents), Some(used_at), Some(mark)) =
+            (self.snapshot, self.clipboard_used_at, self.mark)
+        {
+            schedule_restore(
+                mark,
+                contents,
+                used_at,
+                Duration::from_millis(self.config.restore_clipboard_delay),
+            );
+        }
+    }
+}
+
+// 验证码的一种投递方式；新的集成只需实现此 trait 并在 SinkConfig 中注册
+pub trait DeliverySink {
+    fn name(&self) -> String;
+
+    fn deliver(&self, ctx: &mut DeliveryContext) -> Result<(), Box<dyn Error>>;
+}
+
+// 各个投递方式的结果，按执行顺序排列
+#[derive(Debug, Default)]
+pub struct DeliveryReport {
+    pub results: Vec<(String, Result<(), String>)>,
+    // 验证码已写入剪贴板或输入到前台应用
+    pub handed_over: bool,
+}
+
+impl DeliveryReport {
+    // 至少有一种方式成功
+    pub fn delivered(&self) -> bool {
+        self.results.iter().any(|(_, result)| result.is_ok())
+    }
+}
+
+// 按顺序执行各个投递方式，单个失败不影响后续方式，并分别记录结果
+pub fn deliver(sinks: &[Box<dyn DeliverySink>], mut ctx: DeliveryContext) -> DeliveryReport {
+    let mut report = DeliveryReport::default();
+    for sink in sinks {
+        let result = sink.deliver(&mut ctx).map_err(|e| e.to_string());
+        match &result {
+            Ok(_) => info!("{}: {}", t!("delivery-succeeded"), sink.name()),
+            Err(e) => error!("{}: {}: {}", t!("delivery-failed"), sink.name(), e),
+        }
+        report.results.push((sink.name(), result));
+    }
+    report.handed_over = ctx.handed_over;
+    ctx.finish();
+    report
+}
+
+pub struct ClipboardSink;
+
+impl DeliverySink for ClipboardSink {
+    fn name(&self) -> String {
+        "clipboard".to_string()
+    }
+
+    fn deliver(&self, ctx: &mut DeliveryContext) -> Result<(), Box<dyn Error>> {
+        ctx.set_clipboard()
+    }
+}
+
+// 前台应用不允许自动粘贴时改用的投递方式
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum PasteFallback {
+    #[default]
+    Clipboard,
+    FloatWindow,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum PasteTarget {
+    // 可以粘贴，附带识别到的前台应用
+    Paste(Option<FrontmostApp>),
+    Fallback(PasteFallback),
+}
+
+// paste_allowlist 为空时不限制；不为空时无法识别前台应用也不粘贴
+pub fn paste_allowed(allowlist: &[String], app: Option<&FrontmostApp>) -> bool {
+    allowlist.is_empty()
+        || app.is_some_and(|app| allowlist.iter().any(|pattern| app.matches(pattern)))
+}
+
+pub fn paste_target(config: &MAConfig, apps: &dyn FrontmostAppProvider) -> PasteTarget {
+    let app = apps.frontmost_app();
+    if paste_allowed(&config.paste_allowlist, app.as_ref()) {
+        PasteTarget::Paste(app)
+    } else {
+        info!("{}: {:?}", t!("paste-not-allowed"), app);
+        PasteTarget::Fallback(config.paste_fallback)
+    }
+}
+
+pub struct PasteSink {
+    pub apps: Box<dyn FrontmostAppProvider>,
+}
+
+impl DeliverySink for PasteSink {
+    fn name(&self) -> String {
+        "paste".to_string()
+    }
+
+    // 前台应用不允许粘贴时改用 paste_fallback；配置了按键序列时按序列执行，代替 auto_return
+    fn deliver(&self, ctx: &mut DeliveryContext) -> Result<(), Box<dyn Error>> {
+        let app = match paste_target(ctx.config, self.apps.as_ref()) {
+            PasteTarget::Paste(app) => app,
+            PasteTarget::Fallback(PasteFallback::Clipboard) => return ClipboardSink.deliver(ctx),
+            PasteTarget::Fallback(PasteFallback::FloatWindow) => {
+                return FloatWindowSink.deliver(ctx)
+            }
+        };
+        if let Some(sequence) = key_sequence_for(ctx.config, app.as_ref()) {
+            let actions = parse_key_sequence(sequence)?;
+            let keyboard = keyboard_backend(KeyboardBackendKind::Auto);
+            let code = ctx.code;
+            run_key_sequence(
+                &actions,
+                code,
+                keyboard.as_deref(),
+                Duration::from_millis(default_key_delay()),
+                &mut || {
+                    ctx.set_clipboard()?;
+                    paste_script()?;
+                    ctx.mark_pasted();
+                    Ok(())
+                },
+            )?;
+            ctx.mark_handed_over();
+            return Ok(());
+        }
+        ctx.set_clipboard()?;
+        paste_script()?;
+        ctx.mark_pasted();
+        info!("{}", t!("paste-verification-code"));
+        if ctx.config.auto_return {
+            return_script()?;
+            info!("{}", t!("press-enter"));
+        }
+        Ok(())
+    }
+}
+
+pub struct FloatWindowSink;
+
+impl DeliverySink for FloatWindowSink {
+    fn name(&self) -> String {
+        "float_window".to_string()
+    }
+
+    fn deliver(&self, ctx: &mut DeliveryContext) -> Result<(), Box<dyn Error>> {
+        let from = if ctx.message.is_mail() {
+            t!("mail")
+        } else {
+            t!("imessage")
+        };
+        let _child = open_app(ctx.code.to_string(), from.to_string(), ctx.expires_at);
+        Ok(())
+    }
+}
+
+pub struct WebhookSink {
+    pub url: String,
+    pub secret: Option<String>,
+}
+
+impl DeliverySink for WebhookSink {
+    fn name(&self) -> String {
+        "webhook".to_string()
+    }
+
+    // 通过 curl 发送，配置从标准输入传入，签名不会出现在进程参数中。
+    // 在后台等待 curl 结束，响应缓慢的地址不会推迟后续的投递方式和信息源
+    fn deliver(&self, ctx: &mut DeliveryContext) -> Result<(), Box<dyn Error>> {
+        let body = ctx.payload().to_string();
+        let mut config = String::from("silent\nshow-error\nfail\nmax-time = 10\n");
+        config.push_str("header = \"Content-Type: application/json\"\n");
+        if let Some(secret) = &self.secret {
+            let header = format!("X-MessAuto-Signature: {}", sign(secret, body.as_bytes()));
+            config.push_str(&format!("header = {}\n", curl_quote(&header)));
+        }
+        config.push_str(&format!("data-binary = {}\n", curl_quote(&body)));
+        config.push_str(&format!("url = {}\n", curl_quote(&self.url)));
+        let mut child = Command::new("curl")
+            .arg("--config")
+            .arg("-")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        child
+            .stdin
+            .take()
+            .ok_or("no stdin")?
+            .write_all(config.as_bytes())?;
+        let name = self.name();
+        thread::Builder::new()
+            .name("webhook".to_string())
+            .spawn(move || match child.wait_with_output() {
+                Ok(output) if output.status.success() => {}
+                Ok(output) => error!(
+                    "{}: {}: curl exited with {}: {}",
+                    t!("delivery-failed"),
+                    name,
+                    output.status,
+                    String::from_utf8_lossy(&output.stderr).trim()
+                ),
+                Err(e) => error!("{}: {}: {}", t!("delivery-failed"), name, e),
+            })?;
+        Ok(())
+    }
+}
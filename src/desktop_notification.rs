@@ -0,0 +1,83 @@
+use std::{error::Error, thread};
+
+use log::{error, info};
+use notify_rust::Notification;
+use rust_i18n::t;
+
+use crate::{
+    catch_up::unix_now,
+    clipboard::write_clipboard,
+    delivery::{DeliveryContext, DeliverySink},
+};
+
+// 通知的应用名，通知信息源据此忽略自己发出的通知
+pub const NOTIFICATION_APP_NAME: &str = "MessAuto";
+const COPY_ACTION: &str = "copy";
+// 点击通知本身时的动作；macOS 上点击通知本身也以这个名称回调
+const DEFAULT_ACTION: &str = "default";
+
+// 回调的动作是否表示复制。部分版本的 notify-rust 在 macOS 上回调的是按钮文字而不是动作名
+pub fn is_copy_action(action: &str, copy_label: &str) -> bool {
+    action == COPY_ACTION || action == DEFAULT_ACTION || action == copy_label
+}
+
+// 显示带“复制”按钮的系统通知，用户点击按钮或通知本身时调用 on_copy；
+// 等待点击在后台线程中进行，不阻塞调用方
+pub fn show_code_notification<F>(
+    code: &str,
+    sender: Option<&str>,
+    expires_at: Option<i64>,
+    on_copy: F,
+) -> Result<(), Box<dyn Error>>
+where
+    F: FnOnce() + Send + 'static,
+{
+    let mut body = Vec::new();
+    if let Some(sender) = sender {
+        body.push(format!("{} {}", t!("from-label"), sender));
+    }
+    if let Some(expires_at) = expires_at {
+        let minutes = ((expires_at - unix_now()) as f64 / 60.0).ceil().max(0.0);
+        body.push(t!("code-expires-in", minutes = minutes).to_string());
+    }
+    let copy_label = t!("copy").to_string();
+    let mut notification = Notification::new();
+    notification
+        .appname(NOTIFICATION_APP_NAME)
+        .summary(&format!("{}: {}", t!("verification-code"), code))
+        .body(&body.join("\n"));
+    // freedesktop 通知规范中 default 动作不显示为按钮，不需要文字；
+    // macOS 会把每个动作都显示为按钮，所以只注册复制按钮
+    #[cfg(not(target_os = "macos"))]
+    notification.action(DEFAULT_ACTION, "");
+    let handle = notification.action(COPY_ACTION, &copy_label).show()?;
+    thread::spawn(move || {
+        handle.wait_for_action(|action| {
+            if is_copy_action(action, &copy_label) {
+                on_copy();
+            }
+        })
+    });
+    Ok(())
+}
+
+pub struct NotificationSink;
+
+impl DeliverySink for NotificationSink {
+    fn name(&self) -> String {
+        "notification".to_string()
+    }
+
+    fn deliver(&self, ctx: &mut DeliveryContext) -> Result<(), Box<dyn Error>> {
+        let code = ctx.code.to_string();
+        show_code_notification(
+            ctx.code,
+            ctx.message.sender.as_deref(),
+            ctx.expires_at,
+            move || match write_clipboard(&code) {
+                Ok(_) => info!("{}", t!("notification-code-copied")),
+                Err(e) => error!("{}: {:?}", t!("error-set-clipboard"), e),
+            },
+        )
+    }
+}
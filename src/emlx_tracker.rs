@@ -0,0 +1,135 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+// 最后一次文件事件之后等待多久再读取，避免读到写了一半的文件
+const SETTLE_DELAY: Duration = Duration::from_millis(500);
+// 读取或解析失败时的最大重试次数
+const MAX_ATTEMPTS: u32 = 8;
+// 只有 .partial.emlx 时等待完整邮件下载的最长时间
+const PARTIAL_TIMEOUT: Duration = Duration::from_secs(300);
+// 已处理的邮件保留多久，Mail.app 通常在邮件到达后不久才会重写文件（例如标记已读）
+const FINISHED_RETENTION: Duration = Duration::from_secs(3600);
+// 已处理邮件的最多保留条数，超过时丢弃最早的
+const MAX_FINISHED: usize = 1024;
+
+// 把 Mail.app 写入过程中出现的各种文件名映射为最终的 .emlx 路径：
+// 123.emlx.tmp、123.partial.emlx、123.emlx 都对应 123.emlx
+pub fn canonical_emlx_path(path: &Path) -> Option<PathBuf> {
+    let file_name = path.file_name()?.to_str()?;
+    let file_name = file_name.strip_suffix(".tmp").unwrap_or(file_name);
+    let stem = file_name.strip_suffix(".emlx")?;
+    let stem = stem.strip_suffix(".partial").unwrap_or(stem);
+    Some(path.with_file_name(format!("{}.emlx", stem)))
+}
+
+pub fn partial_emlx_path(path: &Path) -> PathBuf {
+    let stem = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    path.with_file_name(format!("{}.partial.emlx", stem))
+}
+
+#[derive(Debug)]
+struct PendingEmlx {
+    first_seen: Instant,
+    next_attempt: Instant,
+    attempts: u32,
+}
+
+// 跟踪每封邮件的创建、修改、重命名事件，直到文件完整可解析
+#[derive(Debug, Default)]
+pub struct EmlxTracker {
+    pending: HashMap<PathBuf, PendingEmlx>,
+    // 已处理的邮件和处理时间，之后的事件（例如修改已读标记、移动到其他邮箱时的重写）不再处理；
+    // 按 FINISHED_RETENTION 和 MAX_FINISHED 淘汰，之后的重写由去重和补发策略兜底
+    finished: HashMap<PathBuf, Instant>,
+}
+
+impl EmlxTracker {
+    pub fn new() -> Self {
+        EmlxTracker::default()
+    }
+
+    // 记录一次文件事件，返回对应的最终 .emlx 路径
+    pub fn observe(&mut self, path: &Path, now: Instant) -> Option<PathBuf> {
+        let canonical = canonical_emlx_path(path)?;
+        if self.finished.contains_key(&canonical) {
+            return None;
+        }
+        let pending = self
+            .pending
+            .entry(canonical.clone())
+            .or_insert(PendingEmlx {
+                first_seen: now,
+                next_attempt: now,
+                attempts: 0,
+            });
+        // 文件仍在变化，推迟读取
+        pending.next_attempt = pending.next_attempt.max(now + SETTLE_DELAY);
+        Some(canonical)
+    }
+
+    // 返回可以尝试读取的完整 .emlx 路径，并丢弃超时的条目
+    pub fn due(&mut self, now: Instant) -> Vec<PathBuf> {
+        let mut due = Vec::new();
+        self.pending.retain(|path, pending| {
+            if pending.next_attempt > now {
+                return true;
+            }
+            if path.exists() {
+                due.push(path.clone());
+                return true;
+            }
+            // 只下载了邮件头，等待 Mail.app 写入完整邮件
+            if partial_emlx_path(path).exists() {
+                pending.next_attempt = now + SETTLE_DELAY;
+                return now.duration_since(pending.first_seen) < PARTIAL_TIMEOUT;
+            }
+            // 临时文件尚未重命名，或者文件已被删除
+            pending.attempts += 1;
+            pending.next_attempt = now + SETTLE_DELAY;
+            pending.attempts < MAX_ATTEMPTS
+        });
+        due.sort();
+        due
+    }
+
+    pub fn mark_done(&mut self, path: &Path, now: Instant) {
+        self.pending.remove(path);
+        self.finished
+            .retain(|_, done| now.duration_since(*done) < FINISHED_RETENTION);
+        if self.finished.len() >= MAX_FINISHED {
+            if let Some(oldest) = self
+                .finished
+                .iter()
+                .min_by_key(|(_, done)| **done)
+                .map(|(path, _)| path.clone())
+            {
+                self.finished.remove(&oldest);
+            }
+        }
+        self.finished.insert(path.to_path_buf(), now);
+    }
+
+    // 读取或解析失败后按指数退避重试，超过次数返回 false 表示放弃
+    pub fn mark_failed(&mut self, path: &Path, now: Instant) -> bool {
+        let Some(pending) = self.pending.get_mut(path) else {
+            return false;
+        };
+        pending.attempts += 1;
+        if pending.attempts >= MAX_ATTEMPTS {
+            self.pending.remove(path);
+            return false;
+        }
+        pending.next_attempt = now + SETTLE_DELAY * 2u32.pow(pending.attempts.min(4));
+        true
+    }
+
+    pub fn is_idle(&self) -> bool {
+        self.pending.is_empty()
+    }
+}
@@ -1,6 +1,14 @@
-use std::{fs::File, thread::sleep, time::Duration};
+use std::{
+    cell::{Cell, RefCell},
+    error::Error,
+    fs::File,
+    rc::Rc,
+    sync::Arc,
+    thread,
+    time::{Duration, Instant},
+};
 
-use arboard::Clipboard;
+#[cfg(target_os = "macos")]
 use i_slint_backend_winit::winit::platform::macos::WindowBuilderExtMacOS;
 use log::{error, info};
 use mouse_position::mouse_position::Mouse;
@@ -9,13 +17,29 @@ use simplelog::{
     ColorChoice, CombinedLogger, ConfigBuilder, LevelFilter, TermLogger, TerminalMode, WriteLogger,
 };
 use MessAuto::{
-    get_old_clipboard_contents, get_sys_locale, log_path, paste_script, read_config,
-    recover_clipboard_contents, return_script,
+    catch_up::unix_now,
+    clipboard::{
+        clear_at, clear_deadline, restore_if_owned, snapshot_clipboard, write_clipboard,
+        ClipboardMark,
+    },
+    clipboard_snapshot::wait_until_released,
+    frontmost::frontmost_app,
+    get_sys_locale,
+    key_sequence::{key_sequence_for, parse_key_sequence, run_key_sequence},
+    keystroke::{default_key_delay, keyboard_backend, KeyboardBackendKind},
+    log_path, paste_script, read_config, return_script,
 };
 
 slint::include_modules!();
 
-pub fn main(code: &str, from_app: &str) -> Result<(), slint::PlatformError> {
+// 窗口关闭后最多继续持有剪贴板这么久，之后即使没有其他程序写入剪贴板也退出
+const CLIPBOARD_HOLD_LIMIT: Duration = Duration::from_secs(300);
+
+pub fn main(
+    code: &str,
+    from_app: &str,
+    expires_at: Option<i64>,
+) -> Result<(), slint::PlatformError> {
     let logger_config = ConfigBuilder::new().build();
 
     CombinedLogger::init(vec![
@@ -45,7 +69,11 @@ pub fn main(code: &str, from_app: &str) -> Result<(), slint::PlatformError> {
         from_app
     );
 
+    #[cfg(target_os = "macos")]
     let mut backend = i_slint_backend_winit::Backend::new().unwrap();
+    #[cfg(not(target_os = "macos"))]
+    let backend = i_slint_backend_winit::Backend::new().unwrap();
+    #[cfg(target_os = "macos")]
     backend.window_builder_hook = Some(Box::new(|builder| {
         builder
             .with_titlebar_buttons_hidden(true)
@@ -88,32 +116,94 @@ pub fn main(code: &str, from_app: &str) -> Result<(), slint::PlatformError> {
         .set_position(slint::PhysicalPosition::new(mouse_pos.0, mouse_pos.1));
 
     let ui_handle = ui.as_weak();
-    let config = read_config();
-    let mut clpb = Clipboard::new().unwrap();
+    let config = Arc::new(read_config());
 
     let captcha = String::from(code);
+    // 粘贴后清空剪贴板的时间，窗口关闭后本进程等到此时再退出
+    let clear_at_deadline = Rc::new(Cell::new(None));
+    let deadline = clear_at_deadline.clone();
+    // 保存剪贴板原内容可能要读取大量数据，粘贴在后台线程中进行，不阻塞界面；
+    // 线程返回保存的原内容，窗口关闭后再恢复
+    let paste_worker = Rc::new(RefCell::new(None));
+    let worker = paste_worker.clone();
+    let restore_delay = Duration::from_millis(config.restore_clipboard_delay);
 
     ui.on_paste_code(move || {
-        let ui = ui_handle.unwrap();
-        let old_clpb_contents = get_old_clipboard_contents();
-
-        clpb.set_text(captcha.as_str()).unwrap();
-        match paste_script() {
-            Ok(_) => info!("{}", t!("paste-verification-code")),
-            Err(e) => error!("{}: {:?}", t!("error-paste-verification-code"), e),
+        if worker.borrow().is_some() {
+            return;
         }
-        if config.auto_return {
-            match return_script() {
-                Ok(_) => info!("{}", t!("press-enter")),
-                Err(e) => error!("{}: {:?}", t!("error-press-enter"), e),
+        deadline.set(clear_deadline(&config, unix_now(), expires_at));
+        let ui_handle = ui_handle.clone();
+        let config = config.clone();
+        let captcha = captcha.clone();
+        worker.replace(Some(thread::spawn(move || {
+            let old_clpb_contents = config
+                .recover_clipboard
+                .then(|| snapshot_clipboard(config.clipboard_snapshot_limit));
+
+            let mut mark = None;
+            let mut pasted_at = None;
+            let mut paste = || -> Result<(), Box<dyn Error>> {
+                write_clipboard(&captcha)?;
+                mark = Some(ClipboardMark::new(&captcha));
+                paste_script()?;
+                pasted_at = Some(Instant::now());
+                Ok(())
+            };
+            // 配置了按键序列时按序列执行，代替 auto_return，与 paste 投递方式一致
+            if let Some(sequence) = key_sequence_for(&config, frontmost_app().as_ref()) {
+                let keyboard = keyboard_backend(KeyboardBackendKind::Auto);
+                let result =
+                    parse_key_sequence(sequence)
+                        .map_err(|e| e.into())
+                        .and_then(|actions| {
+                            run_key_sequence(
+                                &actions,
+                                &captcha,
+                                keyboard.as_deref(),
+                                Duration::from_millis(default_key_delay()),
+                                &mut paste,
+                            )
+                        });
+                if let Err(e) = result {
+                    error!("{}: {:?}", t!("error-paste-verification-code"), e);
+                }
+            } else {
+                match paste() {
+                    Ok(_) => info!("{}", t!("paste-verification-code")),
+                    Err(e) => error!("{}: {:?}", t!("error-paste-verification-code"), e),
+                }
+                if config.auto_return {
+                    match return_script() {
+                        Ok(_) => info!("{}", t!("press-enter")),
+                        Err(e) => error!("{}: {:?}", t!("error-press-enter"), e),
+                    }
+                }
             }
-        }
-        if config.recover_clipboard {
-            // sleep(Duration::from_secs(2));
-            recover_clipboard_contents(old_clpb_contents);
-        }
-        ui.hide().unwrap();
+            let pasted_at = pasted_at.unwrap_or_else(Instant::now);
+            let _ = ui_handle.upgrade_in_event_loop(|ui| {
+                let _ = ui.hide();
+            });
+            // 没有写入剪贴板时不需要恢复
+            old_clpb_contents
+                .zip(mark)
+                .map(|(contents, mark)| (contents, mark, pasted_at))
+        })));
     });
 
-    ui.run()
+    let result = ui.run();
+    let restore = paste_worker
+        .take()
+        .and_then(|worker| worker.join().ok())
+        .flatten();
+    if let Some((contents, mark, pasted_at)) = restore {
+        restore_if_owned(&mark, contents, pasted_at, restore_delay);
+    }
+    if let Some(deadline) = clear_at_deadline.get() {
+        clear_at(code, deadline);
+    }
+    // 写入的验证码或恢复的原内容由本进程提供，其他程序写入剪贴板之前不能退出；
+    // 从清空时间（没有时从窗口关闭）算起最多等待 CLIPBOARD_HOLD_LIMIT
+    wait_until_released(Instant::now() + CLIPBOARD_HOLD_LIMIT);
+    result
 }
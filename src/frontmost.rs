@@ -0,0 +1,123 @@
+#[cfg(target_os = "macos")]
+use osakit::{Language, Script};
+
+// 当前接收键盘输入的应用
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FrontmostApp {
+    // macOS 上是 bundle id，Linux 上是窗口的 WM_CLASS 类名
+    pub id: Option<String>,
+    // 应用名或进程名
+    pub name: Option<String>,
+}
+
+impl FrontmostApp {
+    // 按 id 或名称匹配，不区分大小写
+    pub fn matches(&self, pattern: &str) -> bool {
+        [&self.id, &self.name]
+            .into_iter()
+            .flatten()
+            .any(|value| value.eq_ignore_ascii_case(pattern))
+    }
+}
+
+// 获取前台应用的方式，测试中可以替换为固定结果
+pub trait FrontmostAppProvider {
+    fn frontmost_app(&self) -> Option<FrontmostApp>;
+}
+
+// 通过平台接口获取
+pub struct SystemFrontmostApp;
+
+impl FrontmostAppProvider for SystemFrontmostApp {
+    fn frontmost_app(&self) -> Option<FrontmostApp> {
+        frontmost_app()
+    }
+}
+
+#[cfg(target_os = "macos")]
+pub fn frontmost_app() -> Option<FrontmostApp> {
+    let mut script = Script::new_from_source(
+        Language::AppleScript,
+        "
+        tell application \"System Events\"
+	set frontApp to first application process whose frontmost is true
+	return (bundle identifier of frontApp) & linefeed & (name of frontApp)
+        end tell
+    ",
+    );
+    script.compile().ok()?;
+    let output = script.execute().ok()?;
+    let (id, name) = output.as_str()?.split_once('\n')?;
+    Some(FrontmostApp {
+        id: Some(id.to_string()).filter(|id| !id.is_empty() && id != "missing value"),
+        name: Some(name.to_string()),
+    })
+}
+
+// 读取 EWMH 的 _NET_ACTIVE_WINDOW；Wayland 会话中只能识别 XWayland 窗口
+#[cfg(target_os = "linux")]
+pub fn frontmost_app() -> Option<FrontmostApp> {
+    x11::active_window_app().ok().flatten()
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "linux")))]
+pub fn frontmost_app() -> Option<FrontmostApp> {
+    None
+}
+
+#[cfg(target_os = "linux")]
+mod x11 {
+    use std::{error::Error, fs};
+
+    use x11rb::{
+        connection::Connection,
+        protocol::xproto::{Atom, AtomEnum, ConnectionExt},
+        rust_connection::RustConnection,
+    };
+
+    use super::FrontmostApp;
+
+    fn intern(conn: &RustConnection, name: &str) -> Result<Atom, Box<dyn Error>> {
+        Ok(conn.intern_atom(false, name.as_bytes())?.reply()?.atom)
+    }
+
+    pub fn active_window_app() -> Result<Option<FrontmostApp>, Box<dyn Error>> {
+        let (conn, screen_num) = RustConnection::connect(None)?;
+        let root = conn.setup().roots[screen_num].root;
+        let active_window = intern(&conn, "_NET_ACTIVE_WINDOW")?;
+        let window = conn
+            .get_property(false, root, active_window, AtomEnum::WINDOW, 0, 1)?
+            .reply()?
+            .value32()
+            .and_then(|mut values| values.next())
+            .filter(|window| *window != 0);
+        let Some(window) = window else {
+            return Ok(None);
+        };
+
+        // WM_CLASS 是 "实例名\0类名\0"
+        let wm_class = conn
+            .get_property(false, window, AtomEnum::WM_CLASS, AtomEnum::STRING, 0, 256)?
+            .reply()?
+            .value;
+        let mut wm_class = wm_class
+            .split(|b| *b == 0)
+            .filter(|part| !part.is_empty())
+            .map(|part| String::from_utf8_lossy(part).into_owned());
+        let instance = wm_class.next();
+        let class = wm_class.next();
+
+        let wm_pid = intern(&conn, "_NET_WM_PID")?;
+        let process_name = conn
+            .get_property(false, window, wm_pid, AtomEnum::CARDINAL, 0, 1)?
+            .reply()?
+            .value32()
+            .and_then(|mut values| values.next())
+            .and_then(|pid| fs::read_to_string(format!("/proc/{}/comm", pid)).ok())
+            .map(|comm| comm.trim().to_string());
+        Ok(Some(FrontmostApp {
+            id: class.or(instance.clone()),
+            name: process_name.or(instance),
+        }))
+    }
+}
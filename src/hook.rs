@@ -0,0 +1,176 @@
+use std::{
+    error::Error,
+    io::{Read, Write},
+    process::{Child, Command, ExitStatus, Stdio},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use log::{info, warn};
+use rust_i18n::t;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::delivery::{DeliveryContext, DeliverySink};
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct HookConfig {
+    pub command: String,
+    // 固定参数，验证码不会出现在命令行中
+    #[serde(default)]
+    pub args: Vec<String>,
+    // 超时（秒）后结束命令
+    #[serde(default = "default_hook_timeout")]
+    pub timeout: u64,
+}
+
+fn default_hook_timeout() -> u64 {
+    10
+}
+
+#[derive(Debug)]
+pub struct HookOutcome {
+    // 超时被结束时为空
+    pub status: Option<ExitStatus>,
+    pub stderr: String,
+}
+
+impl HookOutcome {
+    pub fn success(&self) -> bool {
+        self.status.is_some_and(|status| status.success())
+    }
+}
+
+// 把 payload 中的字段转为 MESSAUTO_CODE 等环境变量，空值传空字符串
+fn hook_env(payload: &Value) -> Vec<(String, String)> {
+    [
+        "code",
+        "sender",
+        "source",
+        "category",
+        "received_at",
+        "expires_at",
+    ]
+    .iter()
+    .map(|key| {
+        let value = match &payload[key] {
+            Value::String(s) => s.clone(),
+            Value::Null => String::new(),
+            other => other.to_string(),
+        };
+        (format!("MESSAUTO_{}", key.to_uppercase()), value)
+    })
+    .collect()
+}
+
+// 已启动、尚未等待结束的命令
+pub struct RunningHook {
+    child: Child,
+    stderr_reader: JoinHandle<String>,
+    timeout: Duration,
+}
+
+// 启动命令；payload 以 JSON 写入标准输入，同时放入环境变量
+pub fn spawn_hook(hook: &HookConfig, payload: &Value) -> std::io::Result<RunningHook> {
+    let mut child = Command::new(&hook.command)
+        .args(&hook.args)
+        .envs(hook_env(payload))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    // 命令可能不读标准输入，写入失败不算错误
+    let mut stdin = child.stdin.take().unwrap();
+    let input = payload.to_string();
+    thread::spawn(move || {
+        let _ = stdin.write_all(input.as_bytes());
+    });
+    let mut stderr = child.stderr.take().unwrap();
+    let stderr_reader = thread::spawn(move || {
+        let mut output = String::new();
+        let _ = stderr.read_to_string(&mut output);
+        output
+    });
+    Ok(RunningHook {
+        child,
+        stderr_reader,
+        timeout: Duration::from_secs(hook.timeout),
+    })
+}
+
+impl RunningHook {
+    // 等待命令退出，超时后结束命令
+    pub fn wait(mut self) -> std::io::Result<HookOutcome> {
+        let deadline = Instant::now() + self.timeout;
+        let status = loop {
+            if let Some(status) = self.child.try_wait()? {
+                break Some(status);
+            }
+            if Instant::now() >= deadline {
+                let _ = self.child.kill();
+                let _ = self.child.wait();
+                break None;
+            }
+            thread::sleep(Duration::from_millis(50));
+        };
+        // 命令的子进程可能仍持有 stderr，超时后不再等待
+        let stderr = if status.is_some() {
+            self.stderr_reader.join().unwrap_or_default()
+        } else {
+            String::new()
+        };
+        Ok(HookOutcome { status, stderr })
+    }
+}
+
+// 运行命令直到退出或超时
+pub fn run_hook(hook: &HookConfig, payload: &Value) -> std::io::Result<HookOutcome> {
+    spawn_hook(hook, payload)?.wait()
+}
+
+// 命令启动成功即视为投递成功，启动失败（例如命令不存在）时返回错误；
+// 在后台线程中等待命令结束，不阻塞后续投递方式，退出状态只记录到日志
+pub struct CommandSink(pub HookConfig);
+
+impl DeliverySink for CommandSink {
+    fn name(&self) -> String {
+        format!("command:{}", self.0.command)
+    }
+
+    fn deliver(&self, ctx: &mut DeliveryContext) -> Result<(), Box<dyn Error>> {
+        let hook = self.0.clone();
+        let running = spawn_hook(&hook, &ctx.payload())?;
+        thread::Builder::new()
+            .name("hook".to_string())
+            .spawn(move || match running.wait() {
+                Ok(HookOutcome {
+                    status: Some(status),
+                    stderr,
+                }) => {
+                    let stderr = stderr.trim();
+                    if status.success() {
+                        info!(
+                            "{}: {}: {} {}",
+                            t!("hook-finished"),
+                            hook.command,
+                            status,
+                            stderr
+                        );
+                    } else {
+                        warn!(
+                            "{}: {}: {} {}",
+                            t!("hook-failed"),
+                            hook.command,
+                            status,
+                            stderr
+                        );
+                    }
+                }
+                Ok(HookOutcome { status: None, .. }) => {
+                    warn!("{}: {}", t!("hook-timed-out"), hook.command)
+                }
+                Err(e) => warn!("{}: {}: {}", t!("hook-failed"), hook.command, e),
+            })?;
+        Ok(())
+    }
+}
@@ -0,0 +1,241 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    sync::{Mutex, Once},
+    thread,
+    time::Duration,
+};
+
+use log::{info, warn};
+use rust_i18n::t;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    catch_up::unix_now,
+    imap::ImapSession,
+    local_mail::{locate_maildir_file, resolve_path},
+    mail_scope::pattern_matches,
+    read_config,
+};
+
+// 邮件来自哪里，决定收尾操作如何执行
+#[derive(Debug, Clone, PartialEq)]
+pub enum MailOrigin {
+    Imap {
+        account: String,
+        folder: String,
+        uid: u32,
+    },
+    // Maildir 中投递时的路径（new/ 下），邮件客户端之后可能把它移到 cur/
+    Maildir {
+        path: PathBuf,
+    },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum HousekeepingAction {
+    MarkRead,
+    Move,
+    Delete,
+}
+
+fn default_sender_pattern() -> String {
+    "*".to_string()
+}
+
+// 验证码投递成功后对邮件执行的操作，按顺序匹配第一条规则
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct HousekeepingRule {
+    // 发件人地址的通配符
+    #[serde(default = "default_sender_pattern")]
+    pub sender: String,
+    // IMAP 账户名或 Maildir 路径，为空时匹配所有可写的信息源
+    #[serde(default)]
+    pub source: Option<String>,
+    pub action: HousekeepingAction,
+    // move 的目标：IMAP 文件夹名或 Maildir 目录
+    #[serde(default)]
+    pub folder: Option<String>,
+    #[serde(default)]
+    pub after_minutes: u64,
+}
+
+impl HousekeepingRule {
+    fn matches(&self, origin: &MailOrigin, sender: Option<&str>) -> bool {
+        let source_matches = match (&self.source, origin) {
+            (None, _) => true,
+            (Some(source), MailOrigin::Imap { account, .. }) => source == account,
+            (Some(source), MailOrigin::Maildir { path }) => path.starts_with(resolve_path(source)),
+        };
+        source_matches && pattern_matches(&self.sender, sender.unwrap_or_default())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HousekeepingTask {
+    pub origin: MailOrigin,
+    pub action: HousekeepingAction,
+    pub folder: Option<String>,
+    // 执行时间（unix 秒）
+    pub due: i64,
+}
+
+// 按规则生成收尾任务，没有匹配的规则时返回 None
+pub fn plan_housekeeping(
+    rules: &[HousekeepingRule],
+    origin: &MailOrigin,
+    sender: Option<&str>,
+    now: i64,
+) -> Option<HousekeepingTask> {
+    let rule = rules.iter().find(|rule| rule.matches(origin, sender))?;
+    Some(HousekeepingTask {
+        origin: origin.clone(),
+        action: rule.action,
+        folder: rule.folder.clone(),
+        due: now + rule.after_minutes as i64 * 60,
+    })
+}
+
+static PENDING: Mutex<Vec<HousekeepingTask>> = Mutex::new(Vec::new());
+static START: Once = Once::new();
+
+// 验证码交给用户后调用，按配置的规则安排收尾操作
+pub fn schedule_housekeeping(origin: &MailOrigin, sender: Option<&str>) {
+    let config = read_config();
+    if let Some(task) = plan_housekeeping(&config.mail_housekeeping, origin, sender, unix_now()) {
+        queue_housekeeping(task);
+    }
+}
+
+// 待执行的任务只保存在内存中，到期前退出时会丢失
+pub fn queue_housekeeping(task: HousekeepingTask) {
+    PENDING.lock().unwrap().push(task);
+    START.call_once(|| {
+        thread::spawn(|| loop {
+            thread::sleep(Duration::from_secs(5));
+            let due: Vec<HousekeepingTask> = {
+                let mut pending = PENDING.lock().unwrap();
+                let now = unix_now();
+                let (due, rest) = pending.drain(..).partition(|task| task.due <= now);
+                *pending = rest;
+                due
+            };
+            for task in due {
+                run_task(&task);
+            }
+        });
+    });
+}
+
+// 退出前调用，记录尚未执行而被放弃的任务
+pub fn drop_pending_housekeeping() -> Vec<HousekeepingTask> {
+    let dropped: Vec<HousekeepingTask> = PENDING.lock().unwrap().drain(..).collect();
+    for task in &dropped {
+        warn!(
+            "{}: {:?} {:?}",
+            t!("housekeeping-dropped"),
+            task.action,
+            task.origin
+        );
+    }
+    dropped
+}
+
+fn run_task(task: &HousekeepingTask) {
+    let result = match &task.origin {
+        MailOrigin::Imap {
+            account,
+            folder,
+            uid,
+        } => imap_apply(account, folder, *uid, task.action, task.folder.as_deref()),
+        MailOrigin::Maildir { path } => {
+            maildir_apply(path, task.action, task.folder.as_deref()).map(|_| ())
+        }
+    };
+    match result {
+        Ok(()) => info!(
+            "{}: {:?} {:?}",
+            t!("housekeeping-done"),
+            task.action,
+            task.origin
+        ),
+        Err(e) => warn!(
+            "{}: {:?} {:?}: {}",
+            t!("housekeeping-failed"),
+            task.action,
+            task.origin,
+            e
+        ),
+    }
+}
+
+fn imap_apply(
+    account: &str,
+    folder: &str,
+    uid: u32,
+    action: HousekeepingAction,
+    target: Option<&str>,
+) -> io::Result<()> {
+    let config = read_config();
+    let account = config
+        .imap_accounts
+        .iter()
+        .find(|a| a.name == account)
+        .ok_or_else(|| io::Error::other("IMAP account no longer configured"))?;
+    let password = account
+        .password()
+        .ok_or_else(|| io::Error::other("missing IMAP password"))?;
+    let mut session = ImapSession::connect(account, &password, folder)?;
+    session.apply(uid, action, target)?;
+    session.logout()
+}
+
+// 在 Maildir 中执行收尾操作，返回邮件的新路径（删除时为 None）
+pub fn maildir_apply(
+    path: &Path,
+    action: HousekeepingAction,
+    target: Option<&str>,
+) -> io::Result<Option<PathBuf>> {
+    let current =
+        locate_maildir_file(path).ok_or_else(|| io::Error::from(io::ErrorKind::NotFound))?;
+    let file_name = current
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or_default()
+        .to_string();
+    let maildir = current
+        .parent()
+        .and_then(Path::parent)
+        .ok_or_else(|| io::Error::other("not inside a Maildir"))?;
+    match action {
+        HousekeepingAction::MarkRead => {
+            let (unique, flags) = match file_name.split_once(":2,") {
+                Some((unique, flags)) => (unique.to_string(), flags.to_string()),
+                None => (file_name.clone(), String::new()),
+            };
+            let mut flags: Vec<char> = flags.chars().chain(['S']).collect();
+            flags.sort();
+            flags.dedup();
+            let flags: String = flags.into_iter().collect();
+            let new_path = maildir.join("cur").join(format!("{}:2,{}", unique, flags));
+            fs::rename(&current, &new_path)?;
+            Ok(Some(new_path))
+        }
+        HousekeepingAction::Move => {
+            let target = target.ok_or_else(|| io::Error::other("missing target folder"))?;
+            let subdir = if file_name.contains(":2,") {
+                "cur"
+            } else {
+                "new"
+            };
+            let new_path = resolve_path(target).join(subdir).join(&file_name);
+            fs::rename(&current, &new_path)?;
+            Ok(Some(new_path))
+        }
+        HousekeepingAction::Delete => {
+            fs::remove_file(&current)?;
+            Ok(None)
+        }
+    }
+}
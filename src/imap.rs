@@ -0,0 +1,681 @@
+use std::{
+    error::Error,
+    io::{self, Read, Write},
+    net::TcpStream,
+    time::{Duration, Instant},
+};
+
+use log::{info, warn};
+use native_tls::TlsConnector;
+use rust_i18n::t;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    housekeeping::{HousekeepingAction, MailOrigin},
+    parse_mail, read_secret,
+    source::{IncomingMessage, MessageSource},
+    supervisor::StopSignal,
+};
+
+// RFC 2177 建议客户端至少每 29 分钟重新发起一次 IDLE
+const IDLE_RENEW: Duration = Duration::from_secs(25 * 60);
+// 等待服务器响应的超时时间
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(60);
+
+fn default_imap_port() -> u16 {
+    993
+}
+
+fn default_imap_folders() -> Vec<String> {
+    vec!["INBOX".to_string()]
+}
+
+fn default_poll_interval() -> u64 {
+    60
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ImapSecurity {
+    #[default]
+    Tls,
+    StartTls,
+    // 仅用于本地测试服务器，明文传输密码
+    Plain,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ImapAccount {
+    pub name: String,
+    pub host: String,
+    #[serde(default = "default_imap_port")]
+    pub port: u16,
+    #[serde(default)]
+    pub security: ImapSecurity,
+    pub username: String,
+    // 为空时从 secrets.json 中按账户名读取
+    #[serde(default)]
+    pub password: Option<String>,
+    #[serde(default = "default_imap_folders")]
+    pub folders: Vec<String>,
+    // 服务器不支持 IDLE 时的轮询间隔（秒）
+    #[serde(default = "default_poll_interval")]
+    pub poll_interval: u64,
+}
+
+impl ImapAccount {
+    // 优先使用配置中的密码，否则从 secrets.json 中读取
+    pub fn password(&self) -> Option<String> {
+        self.password.clone().or_else(|| read_secret(&self.name))
+    }
+}
+
+// IMAP 响应中的数据项
+#[derive(Debug, Clone, PartialEq)]
+pub enum ImapValue {
+    Atom(String),
+    Bytes(Vec<u8>),
+    List(Vec<ImapValue>),
+    Nil,
+}
+
+impl ImapValue {
+    fn as_text(&self) -> Option<String> {
+        match self {
+            ImapValue::Atom(s) => Some(s.clone()),
+            ImapValue::Bytes(b) => Some(String::from_utf8_lossy(b).to_string()),
+            _ => None,
+        }
+    }
+}
+
+// 把一行完整响应（字面量已内联）解析为数据项
+pub fn parse_values(data: &[u8]) -> Vec<ImapValue> {
+    let mut pos = 0;
+    parse_list(data, &mut pos, false)
+}
+
+fn parse_list(data: &[u8], pos: &mut usize, nested: bool) -> Vec<ImapValue> {
+    let mut values = Vec::new();
+    while *pos < data.len() {
+        match data[*pos] {
+            b' ' | b'\r' | b'\n' => *pos += 1,
+            b'(' => {
+                *pos += 1;
+                values.push(ImapValue::List(parse_list(data, pos, true)));
+            }
+            b')' => {
+                *pos += 1;
+                if nested {
+                    return values;
+                }
+            }
+            b'"' => {
+                *pos += 1;
+                let mut bytes = Vec::new();
+                while *pos < data.len() && data[*pos] != b'"' {
+                    if data[*pos] == b'\\' && *pos + 1 < data.len() {
+                        *pos += 1;
+                    }
+                    bytes.push(data[*pos]);
+                    *pos += 1;
+                }
+                *pos += 1;
+                values.push(ImapValue::Bytes(bytes));
+            }
+            b'{' => {
+                let end = data[*pos..]
+                    .iter()
+                    .position(|b| *b == b'}')
+                    .map(|n| *pos + n)
+                    .unwrap_or(data.len());
+                let len: usize = std::str::from_utf8(&data[*pos + 1..end])
+                    .ok()
+                    .and_then(|s| s.parse().ok())
+                    .unwrap_or_default();
+                // 跳过 "}\r\n"
+                let start = (end + 3).min(data.len());
+                let stop = (start + len).min(data.len());
+                values.push(ImapValue::Bytes(data[start..stop].to_vec()));
+                *pos = stop;
+            }
+            _ => {
+                let start = *pos;
+                let mut depth = 0;
+                while *pos < data.len() {
+                    let b = data[*pos];
+                    if b == b'[' {
+                        depth += 1;
+                    } else if b == b']' {
+                        depth -= 1;
+                    } else if depth == 0 && matches!(b, b' ' | b'(' | b')' | b'\r' | b'\n') {
+                        break;
+                    }
+                    *pos += 1;
+                }
+                let atom = String::from_utf8_lossy(&data[start..*pos]).to_string();
+                if atom.eq_ignore_ascii_case("NIL") {
+                    values.push(ImapValue::Nil);
+                } else {
+                    values.push(ImapValue::Atom(atom));
+                }
+            }
+        }
+    }
+    values
+}
+
+// 在 BODYSTRUCTURE 中查找正文部分的编号，优先 text/plain，其次 text/html；
+// 顶层就是单一正文时返回 None
+pub fn text_part(structure: &ImapValue) -> Option<String> {
+    let ImapValue::List(items) = structure else {
+        return None;
+    };
+    if !matches!(items.first(), Some(ImapValue::List(_))) {
+        return None;
+    }
+    find_part(items, "", "PLAIN").or_else(|| find_part(items, "", "HTML"))
+}
+
+fn find_part(items: &[ImapValue], prefix: &str, subtype: &str) -> Option<String> {
+    for (i, item) in items.iter().enumerate() {
+        let ImapValue::List(part) = item else {
+            // 子部分之后是 multipart 的子类型等扩展数据
+            break;
+        };
+        let path = format!("{}{}", prefix, i + 1);
+        match part.first() {
+            Some(ImapValue::List(_)) => {
+                if let Some(found) = find_part(part, &format!("{}.", path), subtype) {
+                    return Some(found);
+                }
+            }
+            Some(kind) => {
+                let kind = kind.as_text().unwrap_or_default();
+                let sub = part.get(1).and_then(|v| v.as_text()).unwrap_or_default();
+                if kind.eq_ignore_ascii_case("TEXT") && sub.eq_ignore_ascii_case(subtype) {
+                    return Some(path);
+                }
+            }
+            None => {}
+        }
+    }
+    None
+}
+
+// 取出 FETCH 响应中某个数据项的值，例如 "BODYSTRUCTURE" 或 "BODY[HEADER]"
+fn fetch_item<'a>(values: &'a [ImapValue], name: &str) -> Option<&'a ImapValue> {
+    let items = values.iter().find_map(|v| match v {
+        ImapValue::List(items) => Some(items),
+        _ => None,
+    })?;
+    items
+        .chunks(2)
+        .find(|pair| {
+            pair[0]
+                .as_text()
+                .is_some_and(|key| key.eq_ignore_ascii_case(name))
+        })
+        .and_then(|pair| pair.get(1))
+}
+
+// 去掉顶层邮件头中的 Content-* 字段，改用正文部分自己的 MIME 头
+fn strip_content_headers(header: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut skipping = false;
+    for line in header.split_inclusive(|b| *b == b'\n') {
+        let continuation = line.first().is_some_and(|b| *b == b' ' || *b == b'\t');
+        if !continuation {
+            skipping = line.len() >= 8 && line[..8].eq_ignore_ascii_case(b"content-");
+        }
+        if line == b"\r\n" || line == b"\n" {
+            break;
+        }
+        if !skipping {
+            out.extend_from_slice(line);
+        }
+    }
+    out
+}
+
+fn quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn timed_out(e: &io::Error) -> bool {
+    matches!(
+        e.kind(),
+        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+    )
+}
+
+trait ImapStream: Read + Write + Send {}
+impl<T: Read + Write + Send> ImapStream for T {}
+
+// 一个已登录并选中文件夹的 IMAP 连接
+pub struct ImapSession {
+    stream: Box<dyn ImapStream>,
+    // 用于设置读取超时，与 stream 共享同一个套接字
+    socket: TcpStream,
+    buf: Vec<u8>,
+    tag: u32,
+    capabilities: Vec<String>,
+    account: String,
+    folder: String,
+    last_uid: u32,
+    poll_interval: Duration,
+}
+
+impl ImapSession {
+    // 连接、登录并选中文件夹，之前已有的邮件不会被处理
+    pub fn connect(account: &ImapAccount, password: &str, folder: &str) -> io::Result<Self> {
+        let socket = TcpStream::connect((account.host.as_str(), account.port))?;
+        socket.set_read_timeout(Some(RESPONSE_TIMEOUT))?;
+        let stream: Box<dyn ImapStream> = match account.security {
+            ImapSecurity::Tls => Box::new(tls_connect(&account.host, socket.try_clone()?)?),
+            _ => Box::new(socket.try_clone()?),
+        };
+        let mut session = ImapSession {
+            stream,
+            socket,
+            buf: Vec::new(),
+            tag: 0,
+            capabilities: Vec::new(),
+            account: account.name.clone(),
+            folder: folder.to_string(),
+            last_uid: 0,
+            poll_interval: Duration::from_secs(account.poll_interval.max(1)),
+        };
+        let greeting = session.read_line()?;
+        if !greeting.starts_with(b"* OK") && !greeting.starts_with(b"* PREAUTH") {
+            return Err(io::Error::other("unexpected IMAP greeting"));
+        }
+        if account.security == ImapSecurity::StartTls {
+            session.command("STARTTLS")?;
+            let plain = session.socket.try_clone()?;
+            session.stream = Box::new(tls_connect(&account.host, plain)?);
+        }
+        session.command_with_strings("LOGIN", &[&account.username, password])?;
+        session.capabilities = session
+            .command("CAPABILITY")?
+            .iter()
+            .flat_map(|line| parse_values(line))
+            .filter_map(|v| v.as_text())
+            .map(|s| s.to_uppercase())
+            .collect();
+        session.select(folder)?;
+        Ok(session)
+    }
+
+    fn has_capability(&self, name: &str) -> bool {
+        self.capabilities.iter().any(|c| c == name)
+    }
+
+    pub fn idle_supported(&self) -> bool {
+        self.has_capability("IDLE")
+    }
+
+    fn select(&mut self, folder: &str) -> io::Result<()> {
+        let lines = self.command(&format!("SELECT {}", quote(folder)))?;
+        let uid_next = lines.iter().find_map(|line| {
+            let text = String::from_utf8_lossy(line).to_uppercase();
+            let start = text.find("[UIDNEXT ")? + "[UIDNEXT ".len();
+            let end = text[start..].find(']')? + start;
+            text[start..end].trim().parse::<u32>().ok()
+        });
+        self.last_uid = match uid_next {
+            Some(uid_next) => uid_next.saturating_sub(1),
+            None => self.search_uids("ALL")?.into_iter().max().unwrap_or(0),
+        };
+        Ok(())
+    }
+
+    // 阻塞直到有新邮件（IDLE 或轮询），返回新邮件；stop 返回 true 时提前结束
+    pub fn wait_for_messages(
+        &mut self,
+        stop: &dyn Fn() -> bool,
+    ) -> io::Result<Vec<IncomingMessage>> {
+        loop {
+            if stop() {
+                return Ok(Vec::new());
+            }
+            if self.idle_supported() {
+                self.idle(stop)?;
+            } else {
+                self.sleep_until(Instant::now() + self.poll_interval, stop);
+                self.command("NOOP")?;
+            }
+            let messages = self.fetch_new()?;
+            if !messages.is_empty() {
+                return Ok(messages);
+            }
+        }
+    }
+
+    fn sleep_until(&self, deadline: Instant, stop: &dyn Fn() -> bool) {
+        while Instant::now() < deadline && !stop() {
+            std::thread::sleep(Duration::from_millis(200));
+        }
+    }
+
+    fn idle(&mut self, stop: &dyn Fn() -> bool) -> io::Result<()> {
+        let tag = self.next_tag();
+        self.write_line(&format!("{} IDLE", tag))?;
+        let continuation = self.read_line()?;
+        if !continuation.starts_with(b"+") {
+            return Err(io::Error::other("IDLE rejected"));
+        }
+        // 使用较短的读取超时，以便及时响应停止请求
+        self.socket
+            .set_read_timeout(Some(Duration::from_millis(500)))?;
+        let deadline = Instant::now() + IDLE_RENEW;
+        let result = loop {
+            if stop() || Instant::now() >= deadline {
+                break Ok(());
+            }
+            match self.read_line() {
+                Ok(line) => {
+                    let text = String::from_utf8_lossy(&line).to_uppercase();
+                    if text.starts_with("* ") && text.trim_end().ends_with(" EXISTS") {
+                        break Ok(());
+                    }
+                }
+                Err(e) if timed_out(&e) => continue,
+                Err(e) => break Err(e),
+            }
+        };
+        self.socket.set_read_timeout(Some(RESPONSE_TIMEOUT))?;
+        result?;
+        self.write_line("DONE")?;
+        self.read_tagged(&tag)?;
+        Ok(())
+    }
+
+    fn search_uids(&mut self, criteria: &str) -> io::Result<Vec<u32>> {
+        let lines = self.command(&format!("UID SEARCH {}", criteria))?;
+        Ok(lines
+            .iter()
+            .filter(|line| line.to_ascii_uppercase().starts_with(b"* SEARCH"))
+            .flat_map(|line| {
+                String::from_utf8_lossy(line)
+                    .split_whitespace()
+                    .skip(2)
+                    .filter_map(|uid| uid.parse().ok())
+                    .collect::<Vec<u32>>()
+            })
+            .collect())
+    }
+
+    // 拉取上次之后到达的邮件，只取邮件头和正文文本部分
+    pub fn fetch_new(&mut self) -> io::Result<Vec<IncomingMessage>> {
+        let mut uids: Vec<u32> = self
+            .search_uids(&format!("UID {}:*", self.last_uid + 1))?
+            .into_iter()
+            .filter(|uid| *uid > self.last_uid)
+            .collect();
+        uids.sort();
+        let mut messages = Vec::new();
+        for uid in uids {
+            if let Some(raw) = self.fetch_text(uid)? {
+                if let Some(mut message) = parse_mail(&raw) {
+                    message.source = format!("imap:{}", self.account);
+                    message.origin = Some(MailOrigin::Imap {
+                        account: self.account.clone(),
+                        folder: self.folder.clone(),
+                        uid,
+                    });
+                    messages.push(message);
+                }
+            }
+            self.last_uid = uid;
+        }
+        Ok(messages)
+    }
+
+    fn fetch_text(&mut self, uid: u32) -> io::Result<Option<Vec<u8>>> {
+        let lines = self.command(&format!("UID FETCH {} (BODYSTRUCTURE)", uid))?;
+        let structure = lines
+            .iter()
+            .map(|line| parse_values(line))
+            .find_map(|values| fetch_item(&values, "BODYSTRUCTURE").cloned());
+        let Some(structure) = structure else {
+            return Ok(None);
+        };
+        let part = text_part(&structure);
+        let items = match &part {
+            Some(part) => format!(
+                "BODY.PEEK[HEADER] BODY.PEEK[{}.MIME] BODY.PEEK[{}]",
+                part, part
+            ),
+            None => "BODY.PEEK[HEADER] BODY.PEEK[TEXT]".to_string(),
+        };
+        let lines = self.command(&format!("UID FETCH {} ({})", uid, items))?;
+        let Some(values) = lines
+            .iter()
+            .map(|line| parse_values(line))
+            .find(|values| fetch_item(values, "BODY[HEADER]").is_some())
+        else {
+            return Ok(None);
+        };
+        let bytes = |name: &str| match fetch_item(&values, name) {
+            Some(ImapValue::Bytes(b)) => b.clone(),
+            _ => Vec::new(),
+        };
+        let header = bytes("BODY[HEADER]");
+        let raw = match &part {
+            Some(part) => {
+                let mut raw = strip_content_headers(&header);
+                raw.extend(bytes(&format!("BODY[{}.MIME]", part)));
+                raw.extend(bytes(&format!("BODY[{}]", part)));
+                raw
+            }
+            None => {
+                let mut raw = header;
+                raw.extend(bytes("BODY[TEXT]"));
+                raw
+            }
+        };
+        Ok(Some(raw))
+    }
+
+    // 对已投递验证码的邮件执行收尾操作；不支持 MOVE 的服务器退回 COPY 与删除
+    pub fn apply(
+        &mut self,
+        uid: u32,
+        action: HousekeepingAction,
+        folder: Option<&str>,
+    ) -> io::Result<()> {
+        match action {
+            HousekeepingAction::MarkRead => {
+                self.command(&format!("UID STORE {} +FLAGS.SILENT (\\Seen)", uid))?;
+            }
+            HousekeepingAction::Move => {
+                let folder = folder.ok_or_else(|| io::Error::other("missing target folder"))?;
+                if self.has_capability("MOVE") {
+                    self.command(&format!("UID MOVE {} {}", uid, quote(folder)))?;
+                } else {
+                    self.command(&format!("UID COPY {} {}", uid, quote(folder)))?;
+                    self.expunge(uid)?;
+                }
+            }
+            HousekeepingAction::Delete => self.expunge(uid)?,
+        }
+        Ok(())
+    }
+
+    // 不带 UID 的 EXPUNGE 会一并清除用户自己标记为删除的其他邮件，
+    // 所以服务器不支持 UIDPLUS 时只标记 \Deleted，留给邮件客户端清除
+    fn expunge(&mut self, uid: u32) -> io::Result<()> {
+        self.command(&format!("UID STORE {} +FLAGS.SILENT (\\Deleted)", uid))?;
+        if self.has_capability("UIDPLUS") {
+            self.command(&format!("UID EXPUNGE {}", uid))?;
+        } else {
+            warn!("{}: {}", t!("imap-expunge-skipped"), uid);
+        }
+        Ok(())
+    }
+
+    pub fn logout(mut self) -> io::Result<()> {
+        self.command("LOGOUT").map(|_| ())
+    }
+
+    fn next_tag(&mut self) -> String {
+        self.tag += 1;
+        format!("M{}", self.tag)
+    }
+
+    fn write_line(&mut self, line: &str) -> io::Result<()> {
+        self.stream.write_all(line.as_bytes())?;
+        self.stream.write_all(b"\r\n")?;
+        self.stream.flush()
+    }
+
+    // 发送命令并返回未标记的响应行，命令失败时返回错误
+    fn command(&mut self, command: &str) -> io::Result<Vec<Vec<u8>>> {
+        let tag = self.next_tag();
+        self.write_line(&format!("{} {}", tag, command))?;
+        self.read_tagged(&tag)
+    }
+
+    // 带字符串参数的命令。引号字符串不能包含 8 位字符、NUL 和 CR/LF，这样的参数（如非 ASCII 密码）
+    // 以同步字面量 {n} 发送，发送前等待服务器的 "+" 继续响应
+    fn command_with_strings(&mut self, command: &str, args: &[&str]) -> io::Result<Vec<Vec<u8>>> {
+        let tag = self.next_tag();
+        let mut line = format!("{} {}", tag, command);
+        for arg in args {
+            if arg
+                .bytes()
+                .all(|b| b.is_ascii() && !matches!(b, b'\0' | b'\r' | b'\n'))
+            {
+                line.push(' ');
+                line.push_str(&quote(arg));
+                continue;
+            }
+            line.push_str(&format!(" {{{}}}", arg.len()));
+            self.write_line(&line)?;
+            let continuation = self.read_line()?;
+            if !continuation.starts_with(b"+") {
+                return Err(io::Error::other(
+                    String::from_utf8_lossy(&continuation).trim().to_string(),
+                ));
+            }
+            self.stream.write_all(arg.as_bytes())?;
+            line.clear();
+        }
+        self.write_line(&line)?;
+        self.read_tagged(&tag)
+    }
+
+    fn read_tagged(&mut self, tag: &str) -> io::Result<Vec<Vec<u8>>> {
+        let mut untagged = Vec::new();
+        loop {
+            let line = self.read_line()?;
+            if let Some(status) = line.strip_prefix(format!("{} ", tag).as_bytes()) {
+                if status.to_ascii_uppercase().starts_with(b"OK") {
+                    return Ok(untagged);
+                }
+                return Err(io::Error::other(
+                    String::from_utf8_lossy(status).trim().to_string(),
+                ));
+            }
+            untagged.push(line);
+        }
+    }
+
+    // 读取一行完整的响应，包括行内的 {n} 字面量；超时不会丢失已读取的数据
+    fn read_line(&mut self) -> io::Result<Vec<u8>> {
+        loop {
+            if let Some(len) = complete_line_len(&self.buf) {
+                return Ok(self.buf.drain(..len).collect());
+            }
+            let mut chunk = [0u8; 4096];
+            let n = self.stream.read(&mut chunk)?;
+            if n == 0 {
+                return Err(io::Error::from(io::ErrorKind::UnexpectedEof));
+            }
+            self.buf.extend_from_slice(&chunk[..n]);
+        }
+    }
+}
+
+// IMAP 信息源，每个账户的每个文件夹一个连接；连接断开后由 run_source 退避重连
+pub struct ImapSource {
+    account: ImapAccount,
+    password: String,
+    folder: String,
+    session: Option<ImapSession>,
+}
+
+impl ImapSource {
+    pub fn new(account: ImapAccount, password: String, folder: String) -> Self {
+        ImapSource {
+            account,
+            password,
+            folder,
+            session: None,
+        }
+    }
+}
+
+impl MessageSource for ImapSource {
+    fn name(&self) -> String {
+        format!("imap:{}/{}", self.account.name, self.folder)
+    }
+
+    fn next_messages(&mut self, stop: &StopSignal) -> Result<Vec<IncomingMessage>, Box<dyn Error>> {
+        let session = match &mut self.session {
+            Some(session) => session,
+            None => {
+                let session = ImapSession::connect(&self.account, &self.password, &self.folder)?;
+                info!(
+                    "{}: {}/{} (IDLE: {})",
+                    t!("listening-to-imap"),
+                    self.account.name,
+                    self.folder,
+                    session.idle_supported()
+                );
+                self.session.insert(session)
+            }
+        };
+        session
+            .wait_for_messages(&|| stop.is_stopped())
+            .map_err(|e| {
+                self.session = None;
+                e.into()
+            })
+    }
+}
+
+// 缓冲区中第一行完整响应的长度，尚不完整时返回 None
+fn complete_line_len(buf: &[u8]) -> Option<usize> {
+    let mut start = 0;
+    loop {
+        let end = buf[start..].iter().position(|b| *b == b'\n')? + start + 1;
+        let line = &buf[start..end];
+        let trimmed = line.strip_suffix(b"\r\n").or(line.strip_suffix(b"\n"))?;
+        let literal = trimmed
+            .strip_suffix(b"}")
+            .and_then(|rest| {
+                rest.iter()
+                    .rposition(|b| *b == b'{')
+                    .map(|i| &rest[i + 1..])
+            })
+            .and_then(|n| std::str::from_utf8(n).ok()?.parse::<usize>().ok());
+        match literal {
+            Some(len) => {
+                if buf.len() < end + len {
+                    return None;
+                }
+                start = end + len;
+            }
+            None => return Some(end),
+        }
+    }
+}
+
+fn tls_connect(host: &str, socket: TcpStream) -> io::Result<native_tls::TlsStream<TcpStream>> {
+    let connector = TlsConnector::new().map_err(io::Error::other)?;
+    connector
+        .connect(host, socket)
+        .map_err(|e| io::Error::other(e.to_string()))
+}
@@ -0,0 +1,451 @@
+use std::{
+    collections::HashMap,
+    error::Error,
+    io::{BufRead, BufReader, Write},
+    process::{Child, Command, Stdio},
+    sync::mpsc::{self, Receiver, RecvTimeoutError},
+    thread,
+    time::{Duration, Instant},
+};
+
+use log::info;
+use mail_parser::{decoders::html::html_to_text, DateTime};
+use rust_i18n::t;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::{
+    catch_up::unix_now,
+    read_secret,
+    source::{IncomingMessage, MessageSource},
+    supervisor::StopSignal,
+};
+
+const MAIL_CAPABILITY: &str = "urn:ietf:params:jmap:mail";
+const CORE_CAPABILITY: &str = "urn:ietf:params:jmap:core";
+// 新建时不在所选邮箱中的邮件记录这么久，期间被服务器规则移入所选邮箱时按新邮件处理
+const OUTSIDE_TTL: Duration = Duration::from_secs(600);
+// 取邮件时请求的属性
+const EMAIL_PROPERTIES: [&str; 8] = [
+    "mailboxIds",
+    "from",
+    "subject",
+    "messageId",
+    "receivedAt",
+    "textBody",
+    "htmlBody",
+    "bodyValues",
+];
+
+fn default_jmap_mailboxes() -> Vec<String> {
+    vec!["inbox".to_string()]
+}
+
+fn default_poll_interval() -> u64 {
+    60
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct JmapAccount {
+    pub name: String,
+    // 会话地址，例如 https://api.fastmail.com/jmap/session
+    pub session_url: String,
+    // 设置后使用 Basic 认证，否则把 token 作为 Bearer token
+    #[serde(default)]
+    pub username: Option<String>,
+    // 为空时从 secrets.json 中按账户名读取
+    #[serde(default)]
+    pub token: Option<String>,
+    // 邮箱的角色（inbox）或名称，不区分大小写
+    #[serde(default = "default_jmap_mailboxes")]
+    pub mailboxes: Vec<String>,
+    // 服务器不支持 EventSource 推送时的轮询间隔（秒）
+    #[serde(default = "default_poll_interval")]
+    pub poll_interval: u64,
+}
+
+impl JmapAccount {
+    pub fn token(&self) -> Option<String> {
+        self.token.clone().or_else(|| read_secret(&self.name))
+    }
+}
+
+// curl 配置文件中的字符串需要转义反斜杠和双引号
+pub(crate) fn curl_quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+// 把 JMAP 邮件对象转换为检测流程使用的邮件，优先使用纯文本正文
+pub fn email_to_message(email: &Value) -> IncomingMessage {
+    let values = &email["bodyValues"];
+    let part_text = |key: &str| -> String {
+        email[key]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|part| part["partId"].as_str())
+            .filter_map(|id| values[id]["value"].as_str())
+            .collect::<Vec<&str>>()
+            .join("\n")
+    };
+    let mut body = part_text("textBody");
+    if body.trim().is_empty() {
+        body = html_to_text(&part_text("htmlBody"));
+    }
+    IncomingMessage {
+        source: "jmap".to_string(),
+        sender: email["from"][0]["email"].as_str().map(str::to_string),
+        subject: email["subject"].as_str().map(str::to_string),
+        body,
+        received_at: email["receivedAt"]
+            .as_str()
+            .and_then(DateTime::parse_rfc3339)
+            .map(|d| d.to_timestamp())
+            .unwrap_or_else(unix_now),
+        id: email["messageId"][0].as_str().map(str::to_string),
+        origin: None,
+    }
+}
+
+// 一个 JMAP 账户的会话，记录邮件状态以便只获取新邮件
+pub struct JmapSession {
+    account: JmapAccount,
+    token: String,
+    api_url: String,
+    event_source_url: Option<String>,
+    account_id: String,
+    mailbox_ids: Vec<String>,
+    state: String,
+    events: Option<(Child, Receiver<String>)>,
+    // 最近新建但不在所选邮箱中的邮件 id 和发现的时间
+    outside: HashMap<String, Instant>,
+}
+
+impl JmapSession {
+    // 读取会话信息、解析邮箱并记录当前状态，之前已有的邮件不会被处理
+    pub fn connect(account: &JmapAccount, token: &str) -> Result<Self, Box<dyn Error>> {
+        let mut session = JmapSession {
+            account: account.clone(),
+            token: token.to_string(),
+            api_url: String::new(),
+            event_source_url: None,
+            account_id: String::new(),
+            mailbox_ids: Vec::new(),
+            state: String::new(),
+            events: None,
+            outside: HashMap::new(),
+        };
+        let resource = session.request(&account.session_url, None)?;
+        session.api_url = resource["apiUrl"]
+            .as_str()
+            .ok_or("missing apiUrl")?
+            .to_string();
+        session.event_source_url = resource["eventSourceUrl"].as_str().map(|url| {
+            url.replace("{types}", "Email")
+                .replace("{closeafter}", "no")
+                .replace("{ping}", "60")
+        });
+        session.account_id = resource["primaryAccounts"][MAIL_CAPABILITY]
+            .as_str()
+            .ok_or("no mail account")?
+            .to_string();
+
+        let responses = session.call(json!([
+            ["Mailbox/get", {"accountId": session.account_id, "properties": ["name", "role"]}, "0"],
+            ["Email/get", {"accountId": session.account_id, "ids": []}, "1"]
+        ]))?;
+        session.mailbox_ids = responses[0][1]["list"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter(|mailbox| {
+                account.mailboxes.iter().any(|wanted| {
+                    [&mailbox["role"], &mailbox["name"]]
+                        .iter()
+                        .filter_map(|v| v.as_str())
+                        .any(|v| v.eq_ignore_ascii_case(wanted))
+                })
+            })
+            .filter_map(|mailbox| mailbox["id"].as_str().map(str::to_string))
+            .collect();
+        session.state = responses[1][1]["state"]
+            .as_str()
+            .ok_or("missing Email state")?
+            .to_string();
+        Ok(session)
+    }
+
+    pub fn push_supported(&self) -> bool {
+        self.event_source_url.is_some()
+    }
+
+    fn curl_config(&self) -> String {
+        let auth = match &self.account.username {
+            Some(username) => format!(
+                "user = {}\n",
+                curl_quote(&format!("{}:{}", username, self.token))
+            ),
+            None => format!(
+                "header = {}\n",
+                curl_quote(&format!("Authorization: Bearer {}", self.token))
+            ),
+        };
+        // 认证信息通过标准输入传给 curl，避免出现在进程列表中
+        format!("silent\nfail\nlocation\n{}", auth)
+    }
+
+    fn request(&self, url: &str, body: Option<&Value>) -> Result<Value, Box<dyn Error>> {
+        let mut config = self.curl_config();
+        config.push_str("max-time = 30\n");
+        if let Some(body) = body {
+            config.push_str("header = \"Content-Type: application/json\"\n");
+            config.push_str(&format!(
+                "data-binary = {}\n",
+                curl_quote(&body.to_string())
+            ));
+        }
+        config.push_str(&format!("url = {}\n", curl_quote(url)));
+        let mut child = Command::new("curl")
+            .arg("--config")
+            .arg("-")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+        child
+            .stdin
+            .take()
+            .ok_or("no stdin")?
+            .write_all(config.as_bytes())?;
+        let output = child.wait_with_output()?;
+        if !output.status.success() {
+            return Err(format!("curl exited with {}", output.status).into());
+        }
+        Ok(serde_json::from_slice(&output.stdout)?)
+    }
+
+    fn call(&self, method_calls: Value) -> Result<Vec<Value>, Box<dyn Error>> {
+        let body = json!({
+            "using": [CORE_CAPABILITY, MAIL_CAPABILITY],
+            "methodCalls": method_calls,
+        });
+        let response = self.request(&self.api_url, Some(&body))?;
+        let responses = response["methodResponses"]
+            .as_array()
+            .cloned()
+            .ok_or("missing methodResponses")?;
+        if let Some(error) = responses.iter().find(|r| r[0] == "error") {
+            return Err(format!("JMAP error: {}", error[1]).into());
+        }
+        Ok(responses)
+    }
+
+    fn in_scope(&self, email: &Value) -> bool {
+        self.mailbox_ids
+            .iter()
+            .any(|id| email["mailboxIds"][id].as_bool() == Some(true))
+    }
+
+    fn to_message(&self, email: &Value) -> IncomingMessage {
+        let mut message = email_to_message(email);
+        message.source = format!("jmap:{}", self.account.name);
+        message
+    }
+
+    // 获取上次状态之后新建的邮件，以及新建后才被移入所选邮箱的邮件（例如服务器端规则），
+    // 只取所选邮箱中的纯文本与 HTML 正文
+    pub fn fetch_new(&mut self) -> Result<Vec<IncomingMessage>, Box<dyn Error>> {
+        let mut messages = Vec::new();
+        loop {
+            let responses = self.call(json!([
+                ["Email/changes", {"accountId": self.account_id, "sinceState": self.state}, "0"],
+                ["Email/get", {
+                    "accountId": self.account_id,
+                    "#ids": {"resultOf": "0", "name": "Email/changes", "path": "/created"},
+                    "properties": EMAIL_PROPERTIES,
+                    "fetchTextBodyValues": true,
+                    "fetchHTMLBodyValues": true
+                }, "1"],
+                // 有变化的邮件先只取所在邮箱
+                ["Email/get", {
+                    "accountId": self.account_id,
+                    "#ids": {"resultOf": "0", "name": "Email/changes", "path": "/updated"},
+                    "properties": ["mailboxIds"]
+                }, "2"]
+            ]))?;
+            let changes = &responses[0][1];
+            let now = Instant::now();
+            self.outside
+                .retain(|_, seen| now.duration_since(*seen) < OUTSIDE_TTL);
+            for email in responses[1][1]["list"].as_array().into_iter().flatten() {
+                if self.in_scope(email) {
+                    messages.push(self.to_message(email));
+                } else if let Some(id) = email["id"].as_str() {
+                    self.outside.insert(id.to_string(), now);
+                }
+            }
+            let mut moved = Vec::new();
+            for email in responses[2][1]["list"].as_array().into_iter().flatten() {
+                if let (true, Some(id)) = (self.in_scope(email), email["id"].as_str()) {
+                    if self.outside.remove(id).is_some() {
+                        moved.push(id.to_string());
+                    }
+                }
+            }
+            if !moved.is_empty() {
+                let responses = self.call(json!([
+                    ["Email/get", {
+                        "accountId": self.account_id,
+                        "ids": moved,
+                        "properties": EMAIL_PROPERTIES,
+                        "fetchTextBodyValues": true,
+                        "fetchHTMLBodyValues": true
+                    }, "0"]
+                ]))?;
+                for email in responses[0][1]["list"].as_array().into_iter().flatten() {
+                    if self.in_scope(email) {
+                        messages.push(self.to_message(email));
+                    }
+                }
+            }
+            self.state = changes["newState"]
+                .as_str()
+                .ok_or("missing newState")?
+                .to_string();
+            if changes["hasMoreChanges"].as_bool() != Some(true) {
+                return Ok(messages);
+            }
+        }
+    }
+
+    // 启动 EventSource 连接，由后台线程逐行读取事件
+    fn open_events(&mut self) -> Result<(), Box<dyn Error>> {
+        let url = self.event_source_url.clone().ok_or("no eventSourceUrl")?;
+        let mut config = self.curl_config();
+        config.push_str("no-buffer\nheader = \"Accept: text/event-stream\"\n");
+        config.push_str(&format!("url = {}\n", curl_quote(&url)));
+        let mut child = Command::new("curl")
+            .arg("--config")
+            .arg("-")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+        child
+            .stdin
+            .take()
+            .ok_or("no stdin")?
+            .write_all(config.as_bytes())?;
+        let stdout = child.stdout.take().ok_or("no stdout")?;
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+                if tx.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+        self.events = Some((child, rx));
+        Ok(())
+    }
+
+    fn close_events(&mut self) {
+        if let Some((mut child, _)) = self.events.take() {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+
+    // 等待 state 推送；返回 false 表示推送连接已断开，需要退回轮询
+    fn wait_for_push(&mut self, stop: &dyn Fn() -> bool) -> bool {
+        if self.events.is_none() && self.open_events().is_err() {
+            return false;
+        }
+        let Some((_, rx)) = &self.events else {
+            return false;
+        };
+        loop {
+            if stop() {
+                return true;
+            }
+            match rx.recv_timeout(Duration::from_millis(500)) {
+                Ok(line) if line.starts_with("event:") && line.contains("state") => return true,
+                Ok(_) | Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => {
+                    self.close_events();
+                    return false;
+                }
+            }
+        }
+    }
+
+    // 阻塞直到有新邮件（推送或轮询），stop 返回 true 时提前结束
+    pub fn wait_for_messages(
+        &mut self,
+        stop: &dyn Fn() -> bool,
+    ) -> Result<Vec<IncomingMessage>, Box<dyn Error>> {
+        loop {
+            if stop() {
+                self.close_events();
+                return Ok(Vec::new());
+            }
+            if !self.push_supported() || !self.wait_for_push(stop) {
+                let deadline =
+                    Instant::now() + Duration::from_secs(self.account.poll_interval.max(1));
+                while Instant::now() < deadline && !stop() {
+                    thread::sleep(Duration::from_millis(200));
+                }
+            }
+            let messages = self.fetch_new()?;
+            if !messages.is_empty() {
+                return Ok(messages);
+            }
+        }
+    }
+}
+
+// JMAP 信息源，连接断开后由 run_source 退避重连
+pub struct JmapSource {
+    account: JmapAccount,
+    token: String,
+    session: Option<JmapSession>,
+}
+
+impl JmapSource {
+    pub fn new(account: JmapAccount, token: String) -> Self {
+        JmapSource {
+            account,
+            token,
+            session: None,
+        }
+    }
+}
+
+impl MessageSource for JmapSource {
+    fn name(&self) -> String {
+        format!("jmap:{}", self.account.name)
+    }
+
+    fn next_messages(&mut self, stop: &StopSignal) -> Result<Vec<IncomingMessage>, Box<dyn Error>> {
+        let session = match &mut self.session {
+            Some(session) => session,
+            None => {
+                let session = JmapSession::connect(&self.account, &self.token)?;
+                info!(
+                    "{}: {} (EventSource: {})",
+                    t!("listening-to-jmap"),
+                    self.account.name,
+                    session.push_supported()
+                );
+                self.session.insert(session)
+            }
+        };
+        session
+            .wait_for_messages(&|| stop.is_stopped())
+            .inspect_err(|_| self.session = None)
+    }
+}
+
+impl Drop for JmapSession {
+    fn drop(&mut self) {
+        self.close_events();
+    }
+}
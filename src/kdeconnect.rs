@@ -0,0 +1,141 @@
+use std::{
+    error::Error,
+    sync::mpsc::{self, Receiver, RecvTimeoutError},
+    thread,
+    time::Duration,
+};
+
+use zbus::{
+    blocking::{connection, Connection, MessageIterator},
+    zvariant::Value,
+    MatchRule, Message, MessageType,
+};
+
+use crate::{
+    source::{IncomingMessage, MessageSource, KDECONNECT_SOURCE},
+    supervisor::StopSignal,
+};
+
+const CONVERSATIONS_INTERFACE: &str = "org.kde.kdeconnect.device.conversations";
+// KDE Connect 中 ConversationMessage 的 type 字段，1 表示收到的短信
+const MESSAGE_TYPE_INBOX: i32 = 1;
+
+// 解析 conversationCreated/conversationUpdated 信号携带的 ConversationMessage，
+// D-Bus 签名为 (isa(s)xiixixa(xsss))：event、body、addresses、date（毫秒）、type、
+// read、threadID、uID、subID、attachments；只返回收到的短信
+pub fn parse_conversation_message(value: &Value) -> Option<IncomingMessage> {
+    let value = match value {
+        Value::Value(inner) => inner.as_ref(),
+        other => other,
+    };
+    let Value::Structure(structure) = value else {
+        return None;
+    };
+    let fields = structure.fields();
+    let body = match fields.get(1)? {
+        Value::Str(body) => body.to_string(),
+        _ => return None,
+    };
+    let sender = match fields.get(2)? {
+        Value::Array(addresses) => addresses.iter().find_map(|address| match address {
+            Value::Structure(address) => match address.fields().first() {
+                Some(Value::Str(s)) => Some(s.to_string()),
+                _ => None,
+            },
+            _ => None,
+        }),
+        _ => None,
+    };
+    let (Value::I64(date), Value::I32(kind)) = (fields.get(3)?, fields.get(4)?) else {
+        return None;
+    };
+    if *kind != MESSAGE_TYPE_INBOX {
+        return None;
+    }
+    let id = match (fields.get(6), fields.get(7)) {
+        (Some(Value::I64(thread_id)), Some(Value::I32(uid))) => {
+            Some(format!("{}-{}", thread_id, uid))
+        }
+        _ => None,
+    };
+    Some(IncomingMessage {
+        source: KDECONNECT_SOURCE.to_string(),
+        sender,
+        subject: None,
+        body,
+        received_at: date / 1000,
+        id,
+        origin: None,
+    })
+}
+
+fn parse_signal(message: &Message) -> Option<IncomingMessage> {
+    let header = message.header();
+    let member = header.member()?;
+    if member.as_str() != "conversationCreated" && member.as_str() != "conversationUpdated" {
+        return None;
+    }
+    let body = message.body();
+    let value: Value = body.deserialize().ok()?;
+    parse_conversation_message(&value)
+}
+
+// KDE Connect 短信信息源：订阅会话总线上已配对手机的短信会话信号
+pub struct KdeConnectSource {
+    connection: Connection,
+    rx: Receiver<IncomingMessage>,
+}
+
+impl KdeConnectSource {
+    // address 为空时连接当前用户的会话总线
+    pub fn new(address: Option<&str>) -> zbus::Result<Self> {
+        let connection = match address {
+            Some(address) => connection::Builder::address(address)?.build()?,
+            None => Connection::session()?,
+        };
+        let rule = MatchRule::builder()
+            .msg_type(MessageType::Signal)
+            .interface(CONVERSATIONS_INTERFACE)?
+            .build();
+        // 创建迭代器时就完成订阅，之后发出的信号不会丢失
+        let iterator = MessageIterator::for_match_rule(rule, &connection, None)?;
+        let (tx, rx) = mpsc::channel();
+        // 阻塞读取信号；连接关闭或信息源被丢弃后线程退出
+        thread::spawn(move || {
+            for message in iterator {
+                let Ok(message) = message else {
+                    break;
+                };
+                if let Some(sms) = parse_signal(&message) {
+                    if tx.send(sms).is_err() {
+                        break;
+                    }
+                }
+            }
+        });
+        Ok(KdeConnectSource { connection, rx })
+    }
+}
+
+impl MessageSource for KdeConnectSource {
+    fn name(&self) -> String {
+        KDECONNECT_SOURCE.to_string()
+    }
+
+    fn next_messages(&mut self, stop: &StopSignal) -> Result<Vec<IncomingMessage>, Box<dyn Error>> {
+        while !stop.is_stopped() {
+            match self.rx.recv_timeout(Duration::from_millis(500)) {
+                Ok(message) => return Ok(vec![message]),
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => return Err("D-Bus connection closed".into()),
+            }
+        }
+        Ok(Vec::new())
+    }
+}
+
+impl Drop for KdeConnectSource {
+    fn drop(&mut self) {
+        let _ = self.connection.clone().close();
+    }
+}
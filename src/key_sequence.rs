@@ -0,0 +1,120 @@
+use std::{error::Error, thread::sleep, time::Duration};
+
+use log::info;
+use rust_i18n::t;
+
+use crate::{
+    frontmost::FrontmostApp,
+    keystroke::{Key, KeyboardBackend},
+    MAConfig,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum KeyAction {
+    // 写入剪贴板并粘贴
+    Paste,
+    // 模拟键盘输入整个验证码
+    Type,
+    // 逐个字符输入，tab 为真时字符之间按 Tab；用于每位一个输入框的验证码
+    TypeSplit { tab: bool },
+    Key(Key),
+    // 等待的毫秒数
+    Delay(u64),
+}
+
+// 解析 "{paste}{delay 200}{tab}{enter}" 形式的按键序列，动作名不区分大小写，动作之间的空白被忽略
+pub fn parse_key_sequence(sequence: &str) -> Result<Vec<KeyAction>, String> {
+    let mut actions = Vec::new();
+    let mut rest = sequence.trim();
+    while !rest.is_empty() {
+        let body = rest
+            .strip_prefix('{')
+            .ok_or_else(|| format!("expected '{{' at \"{}\"", rest))?;
+        let (token, tail) = body
+            .split_once('}')
+            .ok_or_else(|| format!("unclosed '{{' at \"{}\"", rest))?;
+        let token = token.to_lowercase();
+        let mut words = token.split_whitespace();
+        let action = match (words.next(), words.next(), words.next()) {
+            (Some("paste"), None, _) => KeyAction::Paste,
+            (Some("type"), None, _) => KeyAction::Type,
+            (Some("type-split"), None, _) => KeyAction::TypeSplit { tab: false },
+            (Some("type-split"), Some("tab"), None) => KeyAction::TypeSplit { tab: true },
+            (Some("tab"), None, _) => KeyAction::Key(Key::Tab),
+            (Some("enter"), None, _) => KeyAction::Key(Key::Enter),
+            (Some("delay"), Some(ms), None) => KeyAction::Delay(
+                ms.parse()
+                    .map_err(|_| format!("invalid delay \"{}\"", ms))?,
+            ),
+            _ => return Err(format!("unknown action \"{{{}}}\"", token)),
+        };
+        actions.push(action);
+        rest = tail.trim_start();
+    }
+    Ok(actions)
+}
+
+// 前台应用匹配 key_sequences 中的某一项时使用该项，否则使用 key_sequence。
+// 先按 id 再按名称查找，大小写不同的多个键都匹配时优先完全相同的键，其余按键名排序取第一个，
+// 保证 HashMap 的遍历顺序不影响结果
+pub fn key_sequence_for<'a>(config: &'a MAConfig, app: Option<&FrontmostApp>) -> Option<&'a str> {
+    let lookup = |value: &str| {
+        config.key_sequences.get(value).or_else(|| {
+            config
+                .key_sequences
+                .iter()
+                .filter(|(pattern, _)| pattern.eq_ignore_ascii_case(value))
+                .min_by_key(|(pattern, _)| pattern.as_str())
+                .map(|(_, sequence)| sequence)
+        })
+    };
+    app.and_then(|app| {
+        app.id
+            .as_deref()
+            .and_then(lookup)
+            .or_else(|| app.name.as_deref().and_then(lookup))
+    })
+    .map(|sequence| sequence.as_str())
+    .or(config.key_sequence.as_deref())
+}
+
+// 依次执行按键序列；只有粘贴和等待的序列不需要键盘后端
+pub fn run_key_sequence(
+    actions: &[KeyAction],
+    code: &str,
+    keyboard: Option<&dyn KeyboardBackend>,
+    key_delay: Duration,
+    paste: &mut dyn FnMut() -> Result<(), Box<dyn Error>>,
+) -> Result<(), Box<dyn Error>> {
+    let keyboard = || keyboard.ok_or_else(|| t!("keyboard-backend-unavailable").to_string());
+    for action in actions {
+        match action {
+            KeyAction::Paste => {
+                paste()?;
+                info!("{}", t!("paste-verification-code"));
+            }
+            KeyAction::Type => {
+                let keyboard = keyboard()?;
+                keyboard.type_text(code, key_delay)?;
+                info!("{}: {}", t!("type-verification-code"), keyboard.name());
+            }
+            KeyAction::TypeSplit { tab } => {
+                let keyboard = keyboard()?;
+                for (i, c) in code.chars().enumerate() {
+                    if i > 0 {
+                        sleep(key_delay);
+                        if *tab {
+                            keyboard.press_key(Key::Tab)?;
+                            sleep(key_delay);
+                        }
+                    }
+                    keyboard.type_text(&c.to_string(), key_delay)?;
+                }
+                info!("{}: {}", t!("type-verification-code"), keyboard.name());
+            }
+            KeyAction::Key(key) => keyboard()?.press_key(*key)?,
+            KeyAction::Delay(ms) => sleep(Duration::from_millis(*ms)),
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,297 @@
+use std::{
+    env,
+    error::Error,
+    io::Write,
+    process::{Command, Stdio},
+    thread::sleep,
+    time::Duration,
+};
+
+use log::{info, warn};
+#[cfg(target_os = "macos")]
+use osakit::{Language, Script};
+use rust_i18n::t;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    delivery::{
+        paste_target, ClipboardSink, DeliveryContext, DeliverySink, FloatWindowSink, PasteFallback,
+        PasteTarget,
+    },
+    frontmost::FrontmostAppProvider,
+};
+
+// 模拟键盘输入的方式
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum KeyboardBackendKind {
+    // 按平台和当前会话自动选择
+    #[default]
+    Auto,
+    // macOS System Events
+    AppleScript,
+    // 调用 xdotool，经 X11 XTest 扩展输入
+    Xdotool,
+    // 调用 ydotool（需要 ydotoold），经内核 uinput 虚拟键盘输入，Wayland 和 X11 通用
+    Ydotool,
+    // 调用 wtype，经 Wayland virtual-keyboard 协议输入（wlroots、KDE 等）
+    Wtype,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Key {
+    Enter,
+    Tab,
+}
+
+// 字符之间的默认间隔（毫秒）
+pub fn default_key_delay() -> u64 {
+    30
+}
+
+impl Key {
+    // X11 keysym 名称，xdotool 和 wtype 使用
+    fn keysym(&self) -> &'static str {
+        match self {
+            Key::Enter => "Return",
+            Key::Tab => "Tab",
+        }
+    }
+}
+
+pub trait KeyboardBackend {
+    fn name(&self) -> &'static str;
+
+    // 逐字符输入，字符之间间隔 delay
+    fn type_text(&self, text: &str, delay: Duration) -> Result<(), Box<dyn Error>>;
+
+    fn press_key(&self, key: Key) -> Result<(), Box<dyn Error>>;
+}
+
+// 调用外部工具模拟输入的 Linux 后端
+pub struct ToolBackend(pub KeyboardBackendKind);
+
+impl ToolBackend {
+    pub fn program(&self) -> &'static str {
+        match self.0 {
+            KeyboardBackendKind::Ydotool => "ydotool",
+            KeyboardBackendKind::Wtype => "wtype",
+            _ => "xdotool",
+        }
+    }
+
+    // 要输入的文字从标准输入读取，验证码不会出现在其他用户可见的进程参数中
+    pub fn type_command(&self, delay: Duration) -> Command {
+        let delay = delay.as_millis().to_string();
+        let mut command = Command::new(self.program());
+        match self.0 {
+            KeyboardBackendKind::Ydotool => {
+                command.args(["type", "--key-delay", &delay, "--file", "-"])
+            }
+            KeyboardBackendKind::Wtype => command.args(["-d", &delay, "-"]),
+            _ => command.args(["type", "--clearmodifiers", "--delay", &delay, "--file", "-"]),
+        };
+        command
+    }
+
+    pub fn key_command(&self, key: Key) -> Command {
+        let mut command = Command::new(self.program());
+        match (self.0, key) {
+            // ydotool 使用 Linux 键码：KEY_ENTER、KEY_TAB 按下、抬起
+            (KeyboardBackendKind::Ydotool, Key::Enter) => command.args(["key", "28:1", "28:0"]),
+            (KeyboardBackendKind::Ydotool, Key::Tab) => command.args(["key", "15:1", "15:0"]),
+            (KeyboardBackendKind::Wtype, key) => command.args(["-k", key.keysym()]),
+            (_, key) => command.args(["key", "--clearmodifiers", key.keysym()]),
+        };
+        command
+    }
+}
+
+// input 写入命令的标准输入
+fn run(mut command: Command, input: &str) -> Result<(), Box<dyn Error>> {
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    // 写完后关闭标准输入，命令才会开始输入
+    let written = child.stdin.take().unwrap().write_all(input.as_bytes());
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        return Err(format!(
+            "{:?} exited with {}: {}",
+            command.get_program(),
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )
+        .into());
+    }
+    Ok(written?)
+}
+
+impl KeyboardBackend for ToolBackend {
+    fn name(&self) -> &'static str {
+        self.program()
+    }
+
+    fn type_text(&self, text: &str, delay: Duration) -> Result<(), Box<dyn Error>> {
+        run(self.type_command(delay), text)
+    }
+
+    fn press_key(&self, key: Key) -> Result<(), Box<dyn Error>> {
+        run(self.key_command(key), "")
+    }
+}
+
+#[cfg(target_os = "macos")]
+pub struct AppleScriptBackend;
+
+#[cfg(target_os = "macos")]
+fn system_events(action: &str) -> Result<(), Box<dyn Error>> {
+    let mut script = Script::new_from_source(
+        Language::AppleScript,
+        &format!("tell application \"System Events\" to {}", action),
+    );
+    script.compile()?;
+    script.execute()?;
+    Ok(())
+}
+
+#[cfg(target_os = "macos")]
+impl KeyboardBackend for AppleScriptBackend {
+    fn name(&self) -> &'static str {
+        "applescript"
+    }
+
+    fn type_text(&self, text: &str, delay: Duration) -> Result<(), Box<dyn Error>> {
+        for c in text.chars() {
+            let c = match c {
+                '"' => "\\\"".to_string(),
+                '\\' => "\\\\".to_string(),
+                c => c.to_string(),
+            };
+            system_events(&format!("keystroke \"{}\"", c))?;
+            sleep(delay);
+        }
+        Ok(())
+    }
+
+    fn press_key(&self, key: Key) -> Result<(), Box<dyn Error>> {
+        match key {
+            Key::Enter => system_events("key code 36"),
+            Key::Tab => system_events("key code 48"),
+        }
+    }
+}
+
+// 自动选择时依次尝试的后端：Wayland 会话优先使用 wtype，X11 会话使用 xdotool，
+// ydotool 不依赖图形会话，作为最后的选择
+pub fn candidates(kind: KeyboardBackendKind, wayland: bool, x11: bool) -> Vec<KeyboardBackendKind> {
+    if kind != KeyboardBackendKind::Auto {
+        return vec![kind];
+    }
+    if cfg!(target_os = "macos") {
+        return vec![KeyboardBackendKind::AppleScript];
+    }
+    let mut candidates = Vec::new();
+    if wayland {
+        candidates.push(KeyboardBackendKind::Wtype);
+    } else if x11 {
+        candidates.push(KeyboardBackendKind::Xdotool);
+    }
+    candidates.push(KeyboardBackendKind::Ydotool);
+    candidates
+}
+
+fn in_path(program: &str) -> bool {
+    env::var_os("PATH")
+        .is_some_and(|paths| env::split_paths(&paths).any(|dir| dir.join(program).is_file()))
+}
+
+// 返回第一个可用的后端，外部工具都未安装时记录尝试过的工具并返回空
+pub fn keyboard_backend(kind: KeyboardBackendKind) -> Option<Box<dyn KeyboardBackend>> {
+    let wayland = env::var_os("WAYLAND_DISPLAY").is_some();
+    let x11 = env::var_os("DISPLAY").is_some();
+    let candidates = candidates(kind, wayland, x11);
+    let backend = candidates
+        .iter()
+        .find_map(|kind| -> Option<Box<dyn KeyboardBackend>> {
+            match kind {
+                #[cfg(target_os = "macos")]
+                KeyboardBackendKind::AppleScript => Some(Box::new(AppleScriptBackend)),
+                #[cfg(not(target_os = "macos"))]
+                KeyboardBackendKind::AppleScript => None,
+                KeyboardBackendKind::Auto => None,
+                kind => {
+                    let backend = ToolBackend(*kind);
+                    in_path(backend.program()).then(|| Box::new(backend) as _)
+                }
+            }
+        });
+    if backend.is_none() {
+        let tools: Vec<&str> = candidates
+            .into_iter()
+            .filter(|kind| {
+                !matches!(
+                    kind,
+                    KeyboardBackendKind::Auto | KeyboardBackendKind::AppleScript
+                )
+            })
+            .map(|kind| ToolBackend(kind).program())
+            .collect();
+        warn!("{}: {}", t!("keyboard-tool-not-found"), tools.join(", "));
+    }
+    backend
+}
+
+// 输入验证码，press_enter 时随后按回车
+pub fn type_code(
+    backend: &dyn KeyboardBackend,
+    code: &str,
+    delay: Duration,
+    press_enter: bool,
+) -> Result<(), Box<dyn Error>> {
+    backend.type_text(code, delay)?;
+    info!("{}: {}", t!("type-verification-code"), backend.name());
+    if press_enter {
+        sleep(delay);
+        backend.press_key(Key::Enter)?;
+        info!("{}", t!("press-enter"));
+    }
+    Ok(())
+}
+
+// 逐字符模拟键盘输入验证码，不经过剪贴板
+pub struct KeystrokeSink {
+    pub backend: KeyboardBackendKind,
+    // 字符之间的间隔（毫秒）
+    pub key_delay: u64,
+    pub apps: Box<dyn FrontmostAppProvider>,
+}
+
+impl DeliverySink for KeystrokeSink {
+    fn name(&self) -> String {
+        "keystroke".to_string()
+    }
+
+    // 与 paste 一样受 paste_allowlist 限制，前台应用不允许时改用 paste_fallback
+    fn deliver(&self, ctx: &mut DeliveryContext) -> Result<(), Box<dyn Error>> {
+        match paste_target(ctx.config, self.apps.as_ref()) {
+            PasteTarget::Paste(_) => {}
+            PasteTarget::Fallback(PasteFallback::Clipboard) => return ClipboardSink.deliver(ctx),
+            PasteTarget::Fallback(PasteFallback::FloatWindow) => {
+                return FloatWindowSink.deliver(ctx)
+            }
+        }
+        let backend = keyboard_backend(self.backend)
+            .ok_or_else(|| format!("{}: {:?}", t!("keyboard-backend-unavailable"), self.backend))?;
+        type_code(
+            backend.as_ref(),
+            ctx.code,
+            Duration::from_millis(self.key_delay),
+            ctx.config.auto_return,
+        )?;
+        ctx.mark_handed_over();
+        Ok(())
+    }
+}
@@ -1,29 +1,33 @@
 use std::io::Read;
 use std::thread::sleep;
 use std::{
+    collections::{BTreeMap, HashMap},
     error::Error,
     fs,
     path::{Path, PathBuf},
     process::Command,
+    sync::{mpsc::RecvTimeoutError, OnceLock},
     thread,
-    time::Duration,
+    time::{Duration, Instant, SystemTime},
 };
 
 use arboard::Clipboard;
 use auto_launch::AutoLaunch;
 use emlx::parse_emlx;
-use futures::{
-    channel::mpsc::{channel, Receiver},
-    SinkExt, StreamExt,
-};
 use home::home_dir;
 use log::{error, info, warn};
+#[cfg(target_os = "macos")]
 use macos_accessibility_client::accessibility::{
     application_is_trusted, application_is_trusted_with_prompt,
 };
 use mail_parser::MessageParser;
+#[cfg(target_os = "macos")]
 use native_dialog::{MessageDialog, MessageType};
-use notify::{Config, Event, RecommendedWatcher, RecursiveMode, Watcher};
+use notify::{
+    event::{EventKind, ModifyKind},
+    Event, RecommendedWatcher, RecursiveMode, Watcher,
+};
+#[cfg(target_os = "macos")]
 use osakit::{Language, Script};
 use regex_lite::Regex;
 use rust_i18n::t;
@@ -33,6 +37,54 @@ use tray_icon::{
     TrayIconBuilder,
 };
 
+pub mod catch_up;
+pub mod clipboard;
+pub mod clipboard_snapshot;
+pub mod dedup;
+pub mod delivery;
+pub mod desktop_notification;
+pub mod emlx_tracker;
+pub mod frontmost;
+pub mod hook;
+pub mod housekeeping;
+pub mod imap;
+pub mod jmap;
+#[cfg(target_os = "linux")]
+pub mod kdeconnect;
+pub mod key_sequence;
+pub mod keystroke;
+pub mod local_mail;
+pub mod mail_scope;
+#[cfg(target_os = "linux")]
+pub mod notifications;
+pub mod pipeline;
+#[cfg(unix)]
+pub mod socket;
+pub mod source;
+pub mod supervisor;
+pub mod webhook;
+
+use catch_up::{
+    activated_at, catch_up_action, default_catch_up_max_age, unix_now, CatchUpAction, CatchUpPolicy,
+};
+use clipboard::default_restore_clipboard_delay;
+use clipboard_snapshot::default_clipboard_snapshot_limit;
+use dedup::{default_dedup_window, is_duplicate_code};
+use delivery::{PasteFallback, SinkConfig};
+use emlx_tracker::EmlxTracker;
+use housekeeping::HousekeepingRule;
+use imap::{ImapAccount, ImapSource};
+use jmap::{JmapAccount, JmapSource};
+use local_mail::LocalMailWatcher;
+use mail_scope::{
+    account_names, default_mailbox_include, discover_mailboxes, mail_data_dir, mail_root,
+    mailbox_selected, owning_mailbox, watch_roots, Mailbox,
+};
+use pipeline::process_message;
+use source::{run_source, IncomingMessage, MessageSource, IMESSAGE_SOURCE};
+use supervisor::{StopSignal, Supervisor};
+use webhook::{WebhookConfig, WebhookSource};
+
 pub const ARGS_APP: &str = "app";
 rust_i18n::i18n!("locales");
 pub fn get_sys_locale() -> &'static str {
@@ -52,6 +104,18 @@ pub struct MAConfig {
     pub auto_paste: bool,
     #[serde(default)]
     pub auto_return: bool,
+    // 粘贴时执行的按键序列，设置后代替 auto_return，例如 "{paste}{delay 200}{tab}{enter}"
+    #[serde(default)]
+    pub key_sequence: Option<String>,
+    // 按前台应用（bundle id、WM_CLASS 或进程名）覆盖 key_sequence，id 匹配的项优先于名称匹配的项
+    #[serde(default)]
+    pub key_sequences: HashMap<String, String>,
+    // 允许自动粘贴或模拟键盘输入的前台应用（bundle id、WM_CLASS 或进程名），为空时不限制
+    #[serde(default)]
+    pub paste_allowlist: Vec<String>,
+    // 前台应用不在 paste_allowlist 中时改为复制到剪贴板或显示浮动窗口
+    #[serde(default)]
+    pub paste_fallback: PasteFallback,
     #[serde(default)]
     pub hide_icon_forever: bool,
     #[serde(default)]
@@ -64,6 +128,62 @@ pub struct MAConfig {
     pub float_window: bool,
     #[serde(default)]
     pub recover_clipboard: bool,
+    // 粘贴完成后等待多少毫秒再恢复剪贴板原内容
+    #[serde(default = "default_restore_clipboard_delay")]
+    pub restore_clipboard_delay: u64,
+    // 保存剪贴板原内容时所有格式的总大小上限（字节），超出的格式不保存。
+    // Linux 上通过 X11 CLIPBOARD 选区保存与恢复所有格式，Wayland 会话中依赖 XWayland；
+    // 没有 XWayland 时只保存与恢复文本和图片
+    #[serde(default = "default_clipboard_snapshot_limit")]
+    pub clipboard_snapshot_limit: usize,
+    // 投递后经过多少秒清空剪贴板，剪贴板已被用户改写时不清空
+    #[serde(default)]
+    pub clear_clipboard_after: Option<u64>,
+    // 在验证码过期时清空剪贴板
+    #[serde(default)]
+    pub clear_clipboard_at_expiry: bool,
+    #[serde(default)]
+    pub listen_own_messages: bool,
+    #[serde(default)]
+    pub listen_group_chats: bool,
+    #[serde(default)]
+    pub catch_up: CatchUpPolicy,
+    #[serde(default = "default_catch_up_max_age")]
+    pub catch_up_max_age: u64,
+    #[serde(default = "default_dedup_window")]
+    pub dedup_window: u64,
+    #[serde(default)]
+    pub dedup_match_sender: bool,
+    #[serde(default = "default_mailbox_include")]
+    pub mailbox_include: Vec<String>,
+    #[serde(default)]
+    pub mailbox_exclude: Vec<String>,
+    #[serde(default)]
+    pub mail_accounts: HashMap<String, bool>,
+    #[serde(default)]
+    pub maildirs: Vec<String>,
+    #[serde(default)]
+    pub mbox_files: Vec<String>,
+    #[serde(default)]
+    pub imap_accounts: Vec<ImapAccount>,
+    #[serde(default)]
+    pub jmap_accounts: Vec<JmapAccount>,
+    #[serde(default)]
+    pub mail_housekeeping: Vec<HousekeepingRule>,
+    #[serde(default)]
+    pub kdeconnect: bool,
+    // 监听其桌面通知的应用名或 desktop-entry，为空时不监听，"*" 表示所有应用
+    #[serde(default)]
+    pub notification_apps: Vec<String>,
+    // 为空时不启动转发短信的 HTTP 监听
+    #[serde(default)]
+    pub webhook: Option<WebhookConfig>,
+    // 在 socket_path() 上接收脚本写入的信息
+    #[serde(default)]
+    pub socket: bool,
+    // 按信息源名称、类型或类别（"mail"、"sms"、"default"）配置的投递方式，按顺序执行
+    #[serde(default)]
+    pub delivery: HashMap<String, Vec<SinkConfig>>,
 }
 
 fn default_flags() -> Vec<String> {
@@ -83,17 +203,82 @@ impl Default for MAConfig {
         MAConfig {
             auto_paste: false,
             auto_return: false,
+            key_sequence: None,
+            key_sequences: HashMap::new(),
+            paste_allowlist: Vec::new(),
+            paste_fallback: PasteFallback::default(),
             hide_icon_forever: false,
             launch_at_login: false,
             flags: default_flags(),
             listening_to_mail: false,
             float_window: false,
             recover_clipboard: false,
+            restore_clipboard_delay: default_restore_clipboard_delay(),
+            clipboard_snapshot_limit: default_clipboard_snapshot_limit(),
+            clear_clipboard_after: None,
+            clear_clipboard_at_expiry: false,
+            listen_own_messages: false,
+            listen_group_chats: false,
+            catch_up: CatchUpPolicy::default(),
+            catch_up_max_age: default_catch_up_max_age(),
+            dedup_window: default_dedup_window(),
+            dedup_match_sender: false,
+            mailbox_include: default_mailbox_include(),
+            mailbox_exclude: Vec::new(),
+            mail_accounts: HashMap::new(),
+            maildirs: Vec::new(),
+            mbox_files: Vec::new(),
+            imap_accounts: Vec::new(),
+            jmap_accounts: Vec::new(),
+            mail_housekeeping: Vec::new(),
+            kdeconnect: false,
+            notification_apps: Vec::new(),
+            webhook: None,
+            socket: false,
+            delivery: HashMap::new(),
         }
     }
 }
 
 impl MAConfig {
+    // 按启动/唤醒补处理策略判断一条信息的处理方式
+    pub fn catch_up_action(&self, received_at: i64) -> CatchUpAction {
+        catch_up_action(
+            self.catch_up,
+            self.catch_up_max_age,
+            received_at,
+            activated_at(),
+            unix_now(),
+        )
+    }
+
+    // 按 mailbox_include/mailbox_exclude/mail_accounts 选出需要监听的邮箱
+    pub fn selected_mailboxes(&self, mailboxes: Vec<Mailbox>) -> Vec<Mailbox> {
+        mailboxes
+            .into_iter()
+            .filter(|m| {
+                mailbox_selected(
+                    m,
+                    &self.mailbox_include,
+                    &self.mailbox_exclude,
+                    &self.mail_accounts,
+                )
+            })
+            .collect()
+    }
+
+    // 窗口期内已投递过的验证码返回 true，否则记录本次投递
+    pub fn is_duplicate(&self, code: &str, sender: Option<&str>, message_id: Option<&str>) -> bool {
+        is_duplicate_code(
+            code,
+            sender,
+            message_id,
+            self.dedup_window,
+            self.dedup_match_sender,
+            unix_now(),
+        )
+    }
+
     // update the local config "~/.config/messauto/messauto.json"
     pub fn update(&self) -> Result<(), Box<dyn Error>> {
         let updated_config_str = serde_json::to_string(&self)?;
@@ -110,6 +295,30 @@ pub fn config_path() -> PathBuf {
     config_path
 }
 
+// 存放密码等敏感信息，与配置文件分开，格式为 {"账户名": "密码"}
+pub fn secrets_path() -> PathBuf {
+    let mut secrets_path = home_dir().unwrap();
+    secrets_path.push(".config");
+    secrets_path.push("messauto");
+    secrets_path.push("secrets.json");
+    secrets_path
+}
+
+// 脚本输入信息源的 Unix 套接字
+pub fn socket_path() -> PathBuf {
+    let mut socket_path = home_dir().unwrap();
+    socket_path.push(".config");
+    socket_path.push("messauto");
+    socket_path.push("messauto.sock");
+    socket_path
+}
+
+pub fn read_secret(name: &str) -> Option<String> {
+    let secrets = fs::read_to_string(secrets_path()).ok()?;
+    let secrets: HashMap<String, String> = serde_json::from_str(&secrets).ok()?;
+    secrets.get(name).cloned()
+}
+
 pub fn log_path() -> PathBuf {
     let mut log_path = home_dir().unwrap();
     log_path.push(".config");
@@ -276,9 +485,13 @@ pub fn auto_launch() -> AutoLaunch {
     let app_name = env!("CARGO_PKG_NAME");
     let app_path = get_current_exe_path();
     let args = &["--minimized", "--hidden"];
-    AutoLaunch::new(app_name, app_path.to_str().unwrap(), false, args)
+    #[cfg(target_os = "macos")]
+    return AutoLaunch::new(app_name, app_path.to_str().unwrap(), false, args);
+    #[cfg(not(target_os = "macos"))]
+    AutoLaunch::new(app_name, app_path.to_str().unwrap(), args)
 }
 
+#[cfg(target_os = "macos")]
 pub fn check_full_disk_access() {
     // 试图访问敏感文件来触发权限请求
     let check_db_path = home_dir()
@@ -311,6 +524,11 @@ pub fn check_full_disk_access() {
     }
 }
 
+// 完全磁盘访问权限只存在于 macOS
+#[cfg(not(target_os = "macos"))]
+pub fn check_full_disk_access() {}
+
+#[cfg(target_os = "macos")]
 pub fn check_script_permissions() -> bool {
     let mut script = Script::new_from_source(
         Language::AppleScript,
@@ -329,6 +547,12 @@ pub fn check_script_permissions() -> bool {
     }
 }
 
+#[cfg(not(target_os = "macos"))]
+pub fn check_script_permissions() -> bool {
+    false
+}
+
+#[cfg(target_os = "macos")]
 pub fn check_accessibility() -> bool {
     if application_is_trusted_with_prompt() && check_script_permissions() {
         return true;
@@ -336,10 +560,21 @@ pub fn check_accessibility() -> bool {
     false
 }
 
+#[cfg(not(target_os = "macos"))]
+pub fn check_accessibility() -> bool {
+    false
+}
+
+#[cfg(target_os = "macos")]
 pub fn check_accessibility_with_no_action() -> bool {
     application_is_trusted()
 }
 
+#[cfg(not(target_os = "macos"))]
+pub fn check_accessibility_with_no_action() -> bool {
+    false
+}
+
 // 检查最新信息是否是验证码类型,并返回关键词来辅助定位验证码
 pub fn check_captcha_or_other<'a>(stdout: &'a str, flags: &'a Vec<String>) -> bool {
     for flag in flags {
@@ -367,15 +602,91 @@ pub fn get_captchas(stdout: &str) -> Vec<String> {
     captcha_vec
 }
 
-// 如果检测到 chat.db 有变动，则提取最近一分钟内最新的一条信息
-pub fn get_message_in_one_minute() -> String {
+// 从信息中提取验证码的有效时长（秒），例如 "valid for 10 minutes"、"5分钟内有效"
+pub fn get_captcha_lifetime(stdout: &str) -> Option<u64> {
+    let patterns = [
+        r"(?i)(?:valid|expires?|expiring)\s+(?:for|in|within|after)\s+(\d+)\s*(hours?|hrs?|minutes?|mins?|seconds?|secs?)\b",
+        r"(\d+)\s*(小时|分钟|秒)内?有效",
+        r"有效期[为是:：]?\s*(\d+)\s*(小时|分钟|秒)",
+    ];
+    for pattern in patterns {
+        let re = Regex::new(pattern).unwrap();
+        if let Some(caps) = re.captures(stdout) {
+            let value: u64 = caps[1].parse().ok()?;
+            let unit = caps[2].to_lowercase();
+            let seconds = if unit.starts_with('h') || unit == "小时" {
+                3600
+            } else if unit.starts_with('m') || unit == "分钟" {
+                60
+            } else {
+                1
+            };
+            return Some(value * seconds);
+        }
+    }
+    None
+}
+
+pub fn chat_db_path() -> PathBuf {
+    home_dir()
+        .expect("获取用户目录失败")
+        .join("Library/Messages/chat.db")
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct ChatMessage {
+    pub text: String,
+    // 信息到达时间（unix 秒）
+    pub date: i64,
+    pub guid: Option<String>,
+    // 发送方的手机号或 Apple ID
+    pub sender: Option<String>,
+}
+
+// 构造查询最近一分钟内最新一条信息的 SQL，默认跳过自己发出的信息和群聊信息
+pub fn build_message_query(config: &MAConfig) -> String {
+    let mut conditions = vec![
+        "datetime(message.date/1000000000 + 978307200,'unixepoch','localtime') > datetime('now','localtime','-60 second')".to_string(),
+        "message.text IS NOT NULL".to_string(),
+    ];
+    if !config.listen_own_messages {
+        conditions.push("message.is_from_me = 0".to_string());
+    }
+    if !config.listen_group_chats {
+        // chat.style 43 为群聊，45 为单聊；没有关联会话的信息按单聊处理
+        conditions.push("(chat.style IS NULL OR chat.style != 43)".to_string());
+    }
+    format!(
+        "SELECT message.text AS text, message.date/1000000000 + 978307200 AS date, \
+         message.guid AS guid, handle.id AS sender FROM message \
+         LEFT JOIN handle ON handle.ROWID = message.handle_id \
+         LEFT JOIN chat_message_join ON chat_message_join.message_id = message.ROWID \
+         LEFT JOIN chat ON chat.ROWID = chat_message_join.chat_id \
+         WHERE {} ORDER BY message.date DESC LIMIT 1;",
+        conditions.join(" AND ")
+    )
+}
+
+pub fn query_latest_message(db_path: &Path, config: &MAConfig) -> Option<ChatMessage> {
     let output = Command::new("sqlite3")
-        .arg(home_dir().expect("获取用户目录失败").join("Library/Messages/chat.db"))
-        .arg("SELECT text FROM message WHERE datetime(date/1000000000 + 978307200,\"unixepoch\",\"localtime\") > datetime(\"now\",\"localtime\",\"-60 second\") ORDER BY date DESC LIMIT 1;")
+        .arg("-json")
+        .arg(db_path)
+        .arg(build_message_query(config))
         .output()
         .expect("sqlite命令运行失败");
 
-    String::from_utf8(output.stdout).unwrap()
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    // 没有结果时 sqlite3 不输出任何内容
+    if stdout.trim().is_empty() {
+        return None;
+    }
+    let messages: Vec<ChatMessage> = serde_json::from_str(&stdout).ok()?;
+    messages.into_iter().next()
+}
+
+// 如果检测到 chat.db 有变动，则提取最近一分钟内最新的一条信息
+pub fn get_message_in_one_minute(config: &MAConfig) -> Option<ChatMessage> {
+    query_latest_message(&chat_db_path(), config)
 }
 
 // 如果信息中包含多个4-8位数字与字母组合（比如公司名称和验证码都是4-8位英文数字组合，例如CSDN）
@@ -399,6 +710,7 @@ pub fn get_real_captcha(stdout: &str) -> String {
     real_captcha
 }
 
+#[cfg(target_os = "macos")]
 pub fn paste_script() -> Result<(), Box<dyn Error>> {
     let mut script = Script::new_from_source(
         Language::AppleScript,
@@ -414,6 +726,7 @@ pub fn paste_script() -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
+#[cfg(target_os = "macos")]
 pub fn return_script() -> Result<(), Box<dyn Error>> {
     let mut script = Script::new_from_source(
         Language::AppleScript,
@@ -429,59 +742,85 @@ pub fn return_script() -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
-pub fn messages_thread() {
-    thread::spawn(move || {
-        let flags = read_config().flags;
-        let check_db_path = home_dir().unwrap().join("Library/Messages/chat.db-wal");
-        let mut last_metadata_modified = fs::metadata(&check_db_path).unwrap().modified().unwrap();
+#[cfg(not(target_os = "macos"))]
+pub fn paste_script() -> Result<(), Box<dyn Error>> {
+    Err("unsupported platform".into())
+}
+
+#[cfg(not(target_os = "macos"))]
+pub fn return_script() -> Result<(), Box<dyn Error>> {
+    Err("unsupported platform".into())
+}
+
+impl ChatMessage {
+    pub fn into_incoming(self) -> IncomingMessage {
+        IncomingMessage {
+            source: IMESSAGE_SOURCE.to_string(),
+            sender: self.sender,
+            subject: None,
+            body: self.text,
+            received_at: self.date,
+            id: self.guid,
+            origin: None,
+        }
+    }
+}
+
+// iMessage 短信：chat.db 的 WAL 文件变化时读取最近一分钟内最新的一条信息
+pub struct SmsSource {
+    wal_path: PathBuf,
+    last_modified: SystemTime,
+}
+
+impl SmsSource {
+    pub fn new() -> Self {
+        SmsSource {
+            wal_path: home_dir().unwrap().join("Library/Messages/chat.db-wal"),
+            // 启动时先检查一次，让启动前刚到达的信息也按补处理策略处理
+            last_modified: SystemTime::UNIX_EPOCH,
+        }
+    }
+}
+
+impl Default for SmsSource {
+    fn default() -> Self {
+        SmsSource::new()
+    }
+}
+
+impl MessageSource for SmsSource {
+    fn name(&self) -> String {
+        IMESSAGE_SOURCE.to_string()
+    }
+
+    fn next_messages(&mut self, stop: &StopSignal) -> Result<Vec<IncomingMessage>, Box<dyn Error>> {
         loop {
-            let now_metadata = fs::metadata(&check_db_path).unwrap().modified().unwrap();
-            if now_metadata != last_metadata_modified {
-                last_metadata_modified = now_metadata;
-                let stdout = get_message_in_one_minute();
-                let captcha_or_other = check_captcha_or_other(&stdout, &flags);
-                if captcha_or_other {
-                    info!("{}", t!("new-verification-code-detected"));
-
-                    let captchas = get_captchas(&stdout);
-                    info!("{}:{:?}", t!("all-possible-codes"), captchas);
-                    let real_captcha = get_real_captcha(&stdout);
-                    info!("{}:{:?}", t!("real-verification-code"), real_captcha);
-                    let mut ctx = Clipboard::new().unwrap();
-                    let old_clipboard_contents = get_old_clipboard_contents();
-
-                    let config = read_config();
-                    if config.float_window {
-                        let _child = open_app(real_captcha, t!("imessage").to_string());
-                    } else if !config.float_window {
-                        ctx.set_text(&real_captcha).unwrap();
-                        if config.auto_paste {
-                            match paste_script() {
-                                Ok(_) => info!("{}", t!("paste-verification-code")),
-                                Err(e) => {
-                                    error!("{}: {:?}", t!("error-paste-verification-code"), e)
-                                }
-                            }
-                        }
-                        if config.auto_return {
-                            match return_script() {
-                                Ok(_) => info!("{}", t!("press-enter")),
-                                Err(e) => error!("{}: {:?}", t!("error-press-enter"), e),
-                            }
-                        }
-                        if config.recover_clipboard {
-                            sleep(Duration::from_secs(2)); // wait applescript to finish
-                            recover_clipboard_contents(old_clipboard_contents);
-                        }
-                    }
+            let modified = fs::metadata(&self.wal_path)?.modified()?;
+            if modified != self.last_modified {
+                self.last_modified = modified;
+                if let Some(message) = get_message_in_one_minute(&read_config()) {
+                    return Ok(vec![message.into_incoming()]);
                 }
-                sleep(Duration::from_secs(5));
             }
-            sleep(Duration::from_secs(1)); // check db change every second
+            // check db change every second
+            if !stop.sleep(Duration::from_secs(1)) {
+                return Ok(Vec::new());
+            }
         }
+    }
+}
+
+#[cfg(target_os = "macos")]
+pub fn messages_thread() {
+    sources().ensure(IMESSAGE_SOURCE, "", |stop| {
+        run_source(&mut SmsSource::new(), &stop, &mut process_message)
     });
 }
 
+// 信息 App 的 chat.db 只存在于 macOS
+#[cfg(not(target_os = "macos"))]
+pub fn messages_thread() {}
+
 pub fn get_current_exe_path() -> PathBuf {
     let mut path = std::env::current_exe().unwrap();
     if path.to_str().unwrap().contains(".app") {
@@ -634,124 +973,347 @@ pub fn replace_old_version() -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
-pub fn mail_thread() {
-    thread::spawn(move || {
-        let mail_path = home_dir().unwrap().join("Library/Mail");
-        let path = String::from(mail_path.to_str().unwrap());
+// 所有信息源的监听线程
+pub fn sources() -> &'static Supervisor {
+    static SOURCES: OnceLock<Supervisor> = OnceLock::new();
+    SOURCES.get_or_init(Supervisor::new)
+}
 
-        futures::executor::block_on(async {
-            if let Err(e) = async_watch(path).await {
-                error!("error: {:?}", e)
+// 按配置启动、重启或停止各个信息源，托盘开关和配置文件变化时调用
+pub fn sync_sources(config: &MAConfig) {
+    let sources = sources();
+    #[cfg(target_os = "linux")]
+    if config.kdeconnect {
+        sources.ensure("sms:kdeconnect", "", run_kdeconnect_source);
+    } else {
+        sources.stop("sms:kdeconnect");
+    }
+    #[cfg(target_os = "linux")]
+    if config.notification_apps.is_empty() {
+        sources.stop("notifications");
+    } else {
+        let key = serde_json::to_string(&config.notification_apps).unwrap_or_default();
+        let allowlist = config.notification_apps.clone();
+        sources.ensure("notifications", &key, move |stop| {
+            run_notification_source(allowlist, stop)
+        });
+    }
+    match &config.webhook {
+        Some(webhook) => match webhook.secret() {
+            Some(secret) => {
+                let key = serde_json::json!([webhook, secret]).to_string();
+                let webhook = webhook.clone();
+                sources.ensure("webhook", &key, move |stop| {
+                    run_webhook_source(&webhook, secret, stop)
+                });
             }
+            None => {
+                warn!("{}", t!("webhook-secret-missing"));
+                sources.stop("webhook");
+            }
+        },
+        None => sources.stop("webhook"),
+    }
+    #[cfg(unix)]
+    if config.socket {
+        sources.ensure("socket", "", run_socket_source);
+    } else {
+        sources.stop("socket");
+    }
+    if !config.listening_to_mail {
+        sources.retain("mail:", &[]);
+        return;
+    }
+    let mut keep = Vec::new();
+
+    let accounts: BTreeMap<&String, &bool> = config.mail_accounts.iter().collect();
+    let key = serde_json::json!([config.mailbox_include, config.mailbox_exclude, accounts]);
+    sources.ensure("mail:apple", &key.to_string(), run_mail_source);
+    keep.push("mail:apple".to_string());
+
+    if !config.maildirs.is_empty() || !config.mbox_files.is_empty() {
+        let key = serde_json::json!([config.maildirs, config.mbox_files]);
+        let (maildirs, mbox_files) = (config.maildirs.clone(), config.mbox_files.clone());
+        sources.ensure("mail:local", &key.to_string(), move |stop| {
+            run_local_mail_source(&maildirs, &mbox_files, stop)
+        });
+        keep.push("mail:local".to_string());
+    }
+
+    for account in &config.imap_accounts {
+        let Some(password) = account.password() else {
+            warn!("{}: {}", t!("imap-password-missing"), account.name);
+            continue;
+        };
+        let key = serde_json::to_string(account).unwrap_or_default();
+        for folder in &account.folders {
+            let name = format!("mail:imap:{}/{}", account.name, folder);
+            let (account, password, folder) = (account.clone(), password.clone(), folder.clone());
+            sources.ensure(&name, &key, move |stop| {
+                let mut source = ImapSource::new(account, password, folder);
+                run_source(&mut source, &stop, &mut process_message)
+            });
+            keep.push(name);
+        }
+    }
+
+    for account in &config.jmap_accounts {
+        let Some(token) = account.token() else {
+            warn!("{}: {}", t!("jmap-token-missing"), account.name);
+            continue;
+        };
+        let key = serde_json::to_string(account).unwrap_or_default();
+        let name = format!("mail:jmap:{}", account.name);
+        let account = account.clone();
+        sources.ensure(&name, &key, move |stop| {
+            let mut source = JmapSource::new(account, token);
+            run_source(&mut source, &stop, &mut process_message)
         });
+        keep.push(name);
+    }
+
+    sources.retain("mail:", &keep);
+}
+
+// 监听配置文件，手动修改邮件相关配置后自动启动、重启或停止对应的信息源
+pub fn config_watch_thread() {
+    thread::spawn(move || {
+        let (tx, rx) = std::sync::mpsc::channel();
+        let Ok(mut watcher) = notify::recommended_watcher(tx) else {
+            return;
+        };
+        let dir = config_path().parent().unwrap().to_path_buf();
+        if let Err(e) = watcher.watch(&dir, RecursiveMode::NonRecursive) {
+            error!("error: {:?}", e);
+            return;
+        }
+        let mut last = fs::read_to_string(config_path()).unwrap_or_default();
+        for event in rx.into_iter().flatten() {
+            if !event.paths.iter().any(|p| p.ends_with("messauto.json")) {
+                continue;
+            }
+            // 不使用 read_config，它会回写配置文件并再次触发事件
+            let Ok(current) = fs::read_to_string(config_path()) else {
+                continue;
+            };
+            if current == last {
+                continue;
+            }
+            last = current;
+            match serde_json::from_str::<MAConfig>(&last) {
+                Ok(config) => sync_sources(&config),
+                Err(e) => warn!("{}: {}", t!("config-parse-failed"), e),
+            }
+        }
     });
 }
 
-fn async_watcher() -> notify::Result<(RecommendedWatcher, Receiver<notify::Result<Event>>)> {
-    let (mut tx, rx) = channel(1);
+// Apple Mail：监听 ~/Library/Mail 中选中的邮箱
+fn run_mail_source(stop: StopSignal) {
+    let Some(data_dir) = mail_data_dir(&mail_root()) else {
+        error!("{}", t!("mail-data-dir-not-found"));
+        return;
+    };
+    let mailboxes = discover_mailboxes(&data_dir, &account_names());
+    let mut accounts: Vec<(&str, &str)> = mailboxes
+        .iter()
+        .map(|m| (m.account_name.as_str(), m.account_id.as_str()))
+        .collect();
+    accounts.dedup();
+    for (name, id) in accounts {
+        info!("{}: {} ({})", t!("mail-account-found"), name, id);
+    }
+    let selected = read_config().selected_mailboxes(mailboxes);
+    for mailbox in &selected {
+        info!(
+            "{}: {}/{}",
+            t!("listening-to-mailbox"),
+            mailbox.account_name,
+            mailbox.name
+        );
+    }
+
+    match AppleMailSource::new(selected) {
+        Ok(mut source) => run_source(&mut source, &stop, &mut process_message),
+        Err(e) => error!("error: {:?}", e),
+    }
+}
+
+// 监听 Thunderbird、mutt、aerc 等客户端使用的本地 Maildir 与 mbox
+fn run_local_mail_source(maildirs: &[String], mbox_files: &[String], stop: StopSignal) {
+    match LocalMailWatcher::new(maildirs, mbox_files) {
+        Ok(mut watcher) => {
+            info!("{}", t!("listening-to-local-mail"));
+            run_source(&mut watcher, &stop, &mut process_message)
+        }
+        Err(e) => error!("error: {:?}", e),
+    }
+}
 
-    let watcher = RecommendedWatcher::new(
-        move |res| {
-            futures::executor::block_on(async {
-                tx.send(res).await.unwrap();
-            })
-        },
-        Config::default(),
-    )?;
-
-    Ok((watcher, rx))
-}
-
-async fn async_watch<P: AsRef<Path>>(path: P) -> notify::Result<()> {
-    let (mut watcher, mut rx) = async_watcher()?;
-
-    // Add a path to be watched. All files and directories at that path and
-    // below will be monitored for changes.
-    watcher.watch(path.as_ref(), RecursiveMode::Recursive)?;
-
-    while let Some(res) = rx.next().await {
-        match res {
-            Ok(event) => {
-                if let notify::event::EventKind::Create(_) = event.kind {
-                    for path in event.paths {
-                        let path = path.to_string_lossy();
-                        if path.contains(".emlx") && path.contains("INBOX.mbox") {
-                            async_std::task::sleep(Duration::from_secs(1)).await; // prevent repeated reading
-                            info!("{}: {:?}", t!("new-email-received"), path);
-                            let path = path.replace(".tmp", "");
-                            let content = read_emlx(&path);
-                            info!("len: {}", content.len());
-
-                            // Protect user privacy
-                            // info!("{}", t!("email-content"));
-
-                            if content.len() < 500 {
-                                let is_captcha =
-                                    check_captcha_or_other(&content, &read_config().flags);
-                                if is_captcha {
-                                    info!("{}", t!("new-verification-email-detected"));
-                                    let captchas = get_captchas(&content);
-                                    info!("{}:{:?}", t!("all-possible-codes"), captchas);
-                                    let real_captcha = get_real_captcha(&content);
-                                    info!("{}:{:?}", t!("real-verification-code"), real_captcha);
-                                    let mut clpb = Clipboard::new().unwrap();
-
-                                    let old_clpb_contents = get_old_clipboard_contents();
-
-                                    let config = read_config();
-                                    if config.float_window {
-                                        let _child = open_app(real_captcha, t!("mail").to_string());
-                                    } else if config.auto_paste {
-                                        clpb.set_text(&real_captcha).unwrap();
-                                        match paste_script() {
-                                            Ok(_) => info!("{}", t!("paste-verification-code")),
-                                            Err(e) => error!(
-                                                "{}: {:?}",
-                                                t!("error-paste-verification-code"),
-                                                e
-                                            ),
-                                        }
-                                        if config.auto_return {
-                                            match return_script() {
-                                                Ok(_) => info!("{}", t!("press-enter")),
-                                                Err(e) => {
-                                                    error!("{}: {:?}", t!("error-press-enter"), e)
-                                                }
-                                            }
-                                        }
-                                        if config.recover_clipboard {
-                                            async_std::task::sleep(Duration::from_secs(2)).await; //wait for pasted
-                                            recover_clipboard_contents(old_clpb_contents);
-                                        }
-                                    }
-                                }
+// 会话总线不可用时直接退出，KDE Connect 守护进程稍后启动不影响信号订阅
+#[cfg(target_os = "linux")]
+fn run_kdeconnect_source(stop: StopSignal) {
+    match kdeconnect::KdeConnectSource::new(None) {
+        Ok(mut source) => {
+            info!("{}", t!("listening-to-kdeconnect"));
+            run_source(&mut source, &stop, &mut process_message)
+        }
+        Err(e) => error!("{}: {}", t!("kdeconnect-unavailable"), e),
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn run_notification_source(allowlist: Vec<String>, stop: StopSignal) {
+    match notifications::NotificationSource::new(None, allowlist) {
+        Ok(mut source) => {
+            info!("{}", t!("listening-to-notifications"));
+            run_source(&mut source, &stop, &mut process_message)
+        }
+        Err(e) => error!("{}: {}", t!("notifications-unavailable"), e),
+    }
+}
+
+fn run_webhook_source(config: &WebhookConfig, secret: String, stop: StopSignal) {
+    match WebhookSource::new(config, secret) {
+        Ok(mut source) => run_source(&mut source, &stop, &mut process_message),
+        Err(e) => error!("{}: {}: {}", t!("webhook-bind-failed"), config.address, e),
+    }
+}
+
+#[cfg(unix)]
+fn run_socket_source(stop: StopSignal) {
+    match socket::SocketSource::new(&socket_path()) {
+        Ok(mut source) => run_source(&mut source, &stop, &mut process_message),
+        Err(e) => error!("{}: {}", t!("socket-bind-failed"), e),
+    }
+}
+
+// Apple Mail 信息源，跟踪 .emlx 的写入过程直到邮件完整可解析
+pub struct AppleMailSource {
+    _watcher: RecommendedWatcher,
+    rx: std::sync::mpsc::Receiver<notify::Result<Event>>,
+    mailboxes: Vec<Mailbox>,
+    tracker: EmlxTracker,
+}
+
+impl AppleMailSource {
+    pub fn new(mailboxes: Vec<Mailbox>) -> notify::Result<Self> {
+        let (tx, rx) = std::sync::mpsc::channel();
+        let mut watcher = notify::recommended_watcher(tx)?;
+        // Only subscribe to the selected mailboxes. Each mailbox is watched
+        // recursively, so nested child mailboxes are filtered below.
+        for root in watch_roots(&mailboxes) {
+            watcher.watch(&root, RecursiveMode::Recursive)?;
+        }
+        Ok(AppleMailSource {
+            _watcher: watcher,
+            rx,
+            mailboxes,
+            tracker: EmlxTracker::new(),
+        })
+    }
+}
+
+impl MessageSource for AppleMailSource {
+    fn name(&self) -> String {
+        "mail".to_string()
+    }
+
+    fn next_messages(&mut self, stop: &StopSignal) -> Result<Vec<IncomingMessage>, Box<dyn Error>> {
+        while !stop.is_stopped() {
+            // 有未完成的邮件时频繁醒来检查，否则每秒醒来一次检查停止请求
+            let wait = if self.tracker.is_idle() {
+                Duration::from_secs(1)
+            } else {
+                Duration::from_millis(250)
+            };
+            match self.rx.recv_timeout(wait) {
+                Ok(Ok(event)) => {
+                    if matches!(
+                        event.kind,
+                        EventKind::Create(_)
+                            | EventKind::Modify(ModifyKind::Data(_))
+                            | EventKind::Modify(ModifyKind::Name(_))
+                            | EventKind::Modify(ModifyKind::Any)
+                    ) {
+                        for path in event.paths {
+                            let in_scope = owning_mailbox(&path)
+                                .map(|owner| self.mailboxes.iter().any(|m| m.path == owner))
+                                .unwrap_or(false);
+                            if in_scope {
+                                self.tracker.observe(&path, Instant::now());
                             }
                         }
                     }
                 }
+                Ok(Err(e)) => error!("watch error: {:?}", e),
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => return Err("mail watcher stopped".into()),
+            }
+
+            let mut messages = Vec::new();
+            for path in self.tracker.due(Instant::now()) {
+                match read_emlx(&path) {
+                    Ok(message) => {
+                        self.tracker.mark_done(&path, Instant::now());
+                        messages.push(message);
+                    }
+                    Err(e) => {
+                        if !self.tracker.mark_failed(&path, Instant::now()) {
+                            warn!("{}: {:?} {}", t!("email-read-failed"), path, e);
+                        }
+                    }
+                }
+            }
+            if !messages.is_empty() {
+                return Ok(messages);
             }
-            Err(e) => error!("watch error: {:?}", e),
         }
+        Ok(Vec::new())
     }
-    Ok(())
 }
 
-fn read_emlx(path: &str) -> String {
-    let mut file = fs::File::open(path).unwrap();
-    let mut buffer = Vec::new();
+// 解析 RFC 822 格式的原始邮件，emlx、Maildir、mbox 与 IMAP 信息源共用
+pub fn parse_mail(raw: &[u8]) -> Option<IncomingMessage> {
+    let message = MessageParser::default().parse(raw)?;
+
+    Some(IncomingMessage {
+        source: "mail".to_string(),
+        sender: message
+            .from()
+            .and_then(|from| from.first())
+            .and_then(|addr| addr.address())
+            .map(str::to_string),
+        subject: message.subject().map(str::to_string),
+        body: message
+            .body_text(0)
+            .map(|body| body.to_string())
+            .unwrap_or_default(),
+        // 没有 Date 头时按收到的时间处理
+        received_at: message
+            .date()
+            .map(|d| d.to_timestamp())
+            .unwrap_or_else(unix_now),
+        id: message.message_id().map(str::to_string),
+        origin: None,
+    })
+}
 
-    file.read_to_end(&mut buffer).unwrap();
+// 读取并解析 emlx 文件；文件不完整或无法解析时返回错误，由调用方决定是否重试
+fn read_emlx(path: &Path) -> Result<IncomingMessage, Box<dyn Error>> {
+    let mut file = fs::File::open(path)?;
+    let mut buffer = Vec::new();
 
-    let parsed = parse_emlx(&buffer).unwrap();
+    file.read_to_end(&mut buffer)?;
 
-    let message = std::str::from_utf8(parsed.message).unwrap();
-    let message = MessageParser::default().parse(message).unwrap();
+    let parsed = parse_emlx(&buffer).map_err(|e| format!("{:?}", e))?;
 
-    message.body_text(0).unwrap().clone().to_string()
+    Ok(parse_mail(parsed.message).ok_or("unable to parse email")?)
 }
 
-pub fn open_app(code: String, from_app: String) -> std::process::Child {
-    start_process(vec![ARGS_APP.to_string(), code, from_app])
+pub fn open_app(code: String, from_app: String, expires_at: Option<i64>) -> std::process::Child {
+    let expires_at = expires_at.map(|t| t.to_string()).unwrap_or_default();
+    start_process(vec![ARGS_APP.to_string(), code, from_app, expires_at])
 }
 
 fn start_process(command_args: Vec<String>) -> std::process::Child {
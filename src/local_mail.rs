@@ -0,0 +1,321 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    error::Error,
+    fs,
+    io::{Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+    sync::mpsc::{channel, Receiver, RecvTimeoutError},
+    time::{Duration, Instant},
+};
+
+use home::home_dir;
+use log::{error, warn};
+use notify::{
+    event::{EventKind, ModifyKind, RenameMode},
+    Event, RecommendedWatcher, RecursiveMode, Watcher,
+};
+use rust_i18n::t;
+
+use crate::{
+    housekeeping::MailOrigin,
+    parse_mail,
+    source::{IncomingMessage, MessageSource},
+    supervisor::StopSignal,
+};
+
+// mbox 文件最后一封邮件在文件静止多久后视为写入完成
+const MBOX_SETTLE_DELAY: Duration = Duration::from_secs(1);
+
+// 展开配置中的 ~ 并解析符号链接，使路径与文件事件中的路径一致
+pub fn resolve_path(path: &str) -> PathBuf {
+    let path = match path.strip_prefix("~/") {
+        Some(rest) => home_dir().expect("获取用户目录失败").join(rest),
+        None => PathBuf::from(path),
+    };
+    if let Ok(path) = fs::canonicalize(&path) {
+        return path;
+    }
+    // 文件尚不存在时只解析所在目录
+    match (path.parent(), path.file_name()) {
+        (Some(parent), Some(name)) => fs::canonicalize(parent)
+            .map(|parent| parent.join(name))
+            .unwrap_or(path),
+        _ => path,
+    }
+}
+
+// 增量读取 mbox 文件中追加的邮件
+#[derive(Debug, Default)]
+pub struct MboxReader {
+    offset: u64,
+    // 从最后一个 "From " 分隔行开始、尚未确认写入完成的原始内容
+    pending: Vec<u8>,
+    last_change: Option<Instant>,
+}
+
+impl MboxReader {
+    // 从文件当前末尾开始读取，已有邮件不再处理
+    pub fn at_end(path: &Path) -> Self {
+        MboxReader {
+            offset: fs::metadata(path).map(|m| m.len()).unwrap_or_default(),
+            ..Default::default()
+        }
+    }
+
+    // 读取新追加的内容，返回已确认完整的邮件（后面已经出现下一封邮件的 From 行）
+    pub fn read_appended(&mut self, path: &Path, now: Instant) -> Vec<Vec<u8>> {
+        let Ok(mut file) = fs::File::open(path) else {
+            return Vec::new();
+        };
+        let len = file.metadata().map(|m| m.len()).unwrap_or_default();
+        if len < self.offset {
+            // 文件被邮件客户端压缩或重写，从新的末尾重新开始
+            self.offset = len;
+            self.pending.clear();
+            return Vec::new();
+        }
+        if len == self.offset {
+            return Vec::new();
+        }
+        let mut appended = Vec::new();
+        if file.seek(SeekFrom::Start(self.offset)).is_err()
+            || file.read_to_end(&mut appended).is_err()
+        {
+            return Vec::new();
+        }
+        self.offset += appended.len() as u64;
+        self.pending.extend_from_slice(&appended);
+        self.last_change = Some(now);
+
+        // 最后一封邮件可能仍在写入，连同分隔行一起留到下一次或静止之后再处理
+        let Some(last) = last_separator(&self.pending) else {
+            return Vec::new();
+        };
+        let rest = self.pending.split_off(last);
+        let complete = std::mem::replace(&mut self.pending, rest);
+        split_mbox(&complete)
+    }
+
+    // 文件静止足够久后，把最后一封邮件视为完整
+    pub fn flush(&mut self, now: Instant) -> Option<Vec<u8>> {
+        let last_change = self.last_change?;
+        if self.pending.is_empty() || now.duration_since(last_change) < MBOX_SETTLE_DELAY {
+            return None;
+        }
+        self.last_change = None;
+        split_mbox(&std::mem::take(&mut self.pending)).pop()
+    }
+}
+
+// 每一行在 data 中的起始位置、内容，以及它是否是 "From " 分隔行（前一行为空行）
+fn mbox_lines(data: &[u8]) -> impl Iterator<Item = (usize, &[u8], bool)> {
+    let mut start = 0;
+    let mut previous_blank = true;
+    data.split_inclusive(|b| *b == b'\n').map(move |line| {
+        let separator = line.starts_with(b"From ") && previous_blank;
+        previous_blank = line == b"\n" || line == b"\r\n";
+        let position = start;
+        start += line.len();
+        (position, line, separator)
+    })
+}
+
+// 最后一个 "From " 分隔行的起始位置
+fn last_separator(data: &[u8]) -> Option<usize> {
+    mbox_lines(data)
+        .filter(|(_, _, separator)| *separator)
+        .map(|(position, _, _)| position)
+        .last()
+}
+
+// 按 "From " 分隔行拆分 mbox 内容，返回去掉分隔行后的各封邮件
+pub fn split_mbox(data: &[u8]) -> Vec<Vec<u8>> {
+    let mut messages: Vec<Vec<u8>> = Vec::new();
+    let mut current: Option<Vec<u8>> = None;
+    for (_, line, separator) in mbox_lines(data) {
+        if separator {
+            if let Some(message) = current.take() {
+                messages.push(message);
+            }
+            current = Some(Vec::new());
+        } else if let Some(message) = current.as_mut() {
+            // mboxrd 格式中正文里的 ">From " 需要还原
+            let unescaped = line
+                .iter()
+                .position(|b| *b != b'>')
+                .filter(|n| *n > 0 && line[*n..].starts_with(b"From "))
+                .map(|_| &line[1..])
+                .unwrap_or(line);
+            message.extend_from_slice(unescaped);
+        }
+    }
+    if let Some(message) = current {
+        messages.push(message);
+    }
+    messages
+}
+
+// 监听 Maildir 的 new/ 目录与 mbox 文件的追加，产出解析后的邮件
+pub struct LocalMailWatcher {
+    _watcher: RecommendedWatcher,
+    rx: Receiver<notify::Result<Event>>,
+    maildirs: Vec<PathBuf>,
+    mboxes: HashMap<PathBuf, MboxReader>,
+    ready: VecDeque<IncomingMessage>,
+}
+
+impl LocalMailWatcher {
+    // maildirs 为 Maildir 根目录（包含 cur/new/tmp），mbox_files 为 mbox 文件路径
+    pub fn new(maildirs: &[String], mbox_files: &[String]) -> notify::Result<Self> {
+        let (tx, rx) = channel();
+        let mut watcher = notify::recommended_watcher(tx)?;
+
+        let mut new_dirs = Vec::new();
+        for maildir in maildirs {
+            let new_dir = resolve_path(maildir).join("new");
+            watcher.watch(&new_dir, RecursiveMode::NonRecursive)?;
+            new_dirs.push(new_dir);
+        }
+        let mut mboxes = HashMap::new();
+        for mbox in mbox_files {
+            let mbox = resolve_path(mbox);
+            // 监听所在目录，这样客户端以替换文件的方式写入时也能收到事件
+            let dir = mbox.parent().unwrap_or(Path::new("."));
+            watcher.watch(dir, RecursiveMode::NonRecursive)?;
+            let reader = MboxReader::at_end(&mbox);
+            mboxes.insert(mbox, reader);
+        }
+
+        Ok(LocalMailWatcher {
+            _watcher: watcher,
+            rx,
+            maildirs: new_dirs,
+            mboxes,
+            ready: VecDeque::new(),
+        })
+    }
+
+    // 等待下一封新邮件，超时返回 None
+    pub fn next_message(&mut self, timeout: Duration) -> Option<IncomingMessage> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            if let Some(message) = self.ready.pop_front() {
+                return Some(message);
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return None;
+            }
+            let wait = (deadline - now).min(Duration::from_millis(250));
+            match self.rx.recv_timeout(wait) {
+                Ok(Ok(event)) => self.handle_event(event),
+                Ok(Err(e)) => error!("watch error: {:?}", e),
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => return None,
+            }
+            let now = Instant::now();
+            let flushed: Vec<Vec<u8>> = self
+                .mboxes
+                .values_mut()
+                .filter_map(|reader| reader.flush(now))
+                .collect();
+            for raw in flushed {
+                self.push_raw(&raw, None);
+            }
+        }
+    }
+
+    fn handle_event(&mut self, event: Event) {
+        let delivered = matches!(
+            event.kind,
+            EventKind::Create(_)
+                | EventKind::Modify(ModifyKind::Name(RenameMode::To))
+                | EventKind::Modify(ModifyKind::Name(RenameMode::Both))
+                | EventKind::Modify(ModifyKind::Name(RenameMode::Any))
+        );
+        let changed = delivered || matches!(event.kind, EventKind::Modify(_));
+        // macOS 上移出目录也会产生 RenameMode::Any 事件，此时文件已不在原处
+        let moved_away = |path: &Path| {
+            matches!(
+                event.kind,
+                EventKind::Modify(ModifyKind::Name(RenameMode::Any))
+            ) && !path.exists()
+        };
+        // 重命名事件的 paths 为 [旧路径, 新路径]，只关心最终路径
+        let paths: Vec<PathBuf> = match event.kind {
+            EventKind::Modify(ModifyKind::Name(RenameMode::Both)) => {
+                event.paths.last().cloned().into_iter().collect()
+            }
+            _ => event.paths.clone(),
+        };
+        for path in paths {
+            if delivered
+                && !moved_away(&path)
+                && path
+                    .parent()
+                    .is_some_and(|p| self.maildirs.iter().any(|d| d == p))
+            {
+                match locate_maildir_file(&path).and_then(|p| fs::read(p).ok()) {
+                    Some(raw) => self.push_raw(&raw, Some(MailOrigin::Maildir { path })),
+                    None => warn!("{}: {:?}", t!("email-read-failed"), path),
+                }
+            } else if changed {
+                if let Some(reader) = self.mboxes.get_mut(&path) {
+                    let messages = reader.read_appended(&path, Instant::now());
+                    for raw in messages {
+                        self.push_raw(&raw, None);
+                    }
+                }
+            }
+        }
+    }
+
+    fn push_raw(&mut self, raw: &[u8], origin: Option<MailOrigin>) {
+        match parse_mail(raw) {
+            Some(mut message) => {
+                message.source = match origin {
+                    Some(_) => "maildir".to_string(),
+                    None => "mbox".to_string(),
+                };
+                message.origin = origin;
+                self.ready.push_back(message)
+            }
+            None => warn!("{}", t!("email-read-failed")),
+        }
+    }
+}
+
+impl MessageSource for LocalMailWatcher {
+    fn name(&self) -> String {
+        "local-mail".to_string()
+    }
+
+    fn next_messages(&mut self, stop: &StopSignal) -> Result<Vec<IncomingMessage>, Box<dyn Error>> {
+        while !stop.is_stopped() {
+            if let Some(message) = self.next_message(Duration::from_secs(1)) {
+                return Ok(vec![message]);
+            }
+        }
+        Ok(Vec::new())
+    }
+}
+
+// Maildir 投递是先写 tmp/ 再重命名到 new/，所以 new/ 中的文件总是完整的；
+// 如果邮件客户端已经把它移到 cur/（文件名追加 ":2,<flags>"），就去 cur/ 中找
+pub fn locate_maildir_file(path: &Path) -> Option<PathBuf> {
+    if path.exists() {
+        return Some(path.to_path_buf());
+    }
+    let unique = path.file_name()?.to_str()?.split(':').next()?.to_string();
+    let cur = path.parent()?.parent()?.join("cur");
+    fs::read_dir(cur)
+        .ok()?
+        .flatten()
+        .find(|entry| {
+            entry
+                .file_name()
+                .to_str()
+                .is_some_and(|name| name.split(':').next() == Some(unique.as_str()))
+        })
+        .map(|entry| entry.path())
+}
@@ -0,0 +1,184 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use home::home_dir;
+use serde::Deserialize;
+
+pub fn default_mailbox_include() -> Vec<String> {
+    vec!["INBOX".to_string()]
+}
+
+// Mail.app 中的一个邮箱，例如 "INBOX" 或 "Work/Verification"
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mailbox {
+    pub account_id: String,
+    pub account_name: String,
+    pub name: String,
+    pub path: PathBuf,
+}
+
+#[derive(Deserialize, Debug)]
+struct AccountRow {
+    id: String,
+    name: Option<String>,
+}
+
+pub fn mail_root() -> PathBuf {
+    home_dir().expect("获取用户目录失败").join("Library/Mail")
+}
+
+// 选出版本号最大的 V* 目录，例如 ~/Library/Mail/V10
+pub fn mail_data_dir(mail_root: &Path) -> Option<PathBuf> {
+    fs::read_dir(mail_root)
+        .ok()?
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            let version = name.strip_prefix('V')?.parse::<u32>().ok()?;
+            Some((version, entry.path()))
+        })
+        .max_by_key(|(version, _)| *version)
+        .map(|(_, path)| path)
+}
+
+// 从系统账户数据库读取账户 UUID 与账户名称的对应关系
+pub fn account_names() -> HashMap<String, String> {
+    let db_path = home_dir()
+        .expect("获取用户目录失败")
+        .join("Library/Accounts/Accounts4.sqlite");
+    let output = Command::new("sqlite3")
+        .arg("-json")
+        .arg(db_path)
+        .arg("SELECT ZIDENTIFIER AS id, COALESCE(ZACCOUNTDESCRIPTION, ZUSERNAME) AS name FROM ZACCOUNT;")
+        .output();
+    let Ok(output) = output else {
+        return HashMap::new();
+    };
+    let rows: Vec<AccountRow> = serde_json::from_slice(&output.stdout).unwrap_or_default();
+    rows.into_iter()
+        .filter_map(|row| Some((row.id, row.name?)))
+        .collect()
+}
+
+// 遍历 V*/<账户 UUID>/ 下的所有 .mbox 目录（包括嵌套的子邮箱）
+pub fn discover_mailboxes(data_dir: &Path, names: &HashMap<String, String>) -> Vec<Mailbox> {
+    let mut mailboxes = Vec::new();
+    let Ok(entries) = fs::read_dir(data_dir) else {
+        return mailboxes;
+    };
+    for entry in entries.flatten() {
+        let account_id = entry.file_name().to_string_lossy().to_string();
+        if !entry.path().is_dir() || account_id == "MailData" {
+            continue;
+        }
+        let account_name = names
+            .get(&account_id)
+            .cloned()
+            .unwrap_or_else(|| account_id.clone());
+        collect_mailboxes(
+            &entry.path(),
+            "",
+            &account_id,
+            &account_name,
+            &mut mailboxes,
+        );
+    }
+    mailboxes.sort_by(|a, b| a.path.cmp(&b.path));
+    mailboxes
+}
+
+fn collect_mailboxes(
+    dir: &Path,
+    prefix: &str,
+    account_id: &str,
+    account_name: &str,
+    mailboxes: &mut Vec<Mailbox>,
+) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let file_name = entry.file_name().to_string_lossy().to_string();
+        let Some(name) = file_name.strip_suffix(".mbox") else {
+            continue;
+        };
+        let name = format!("{}{}", prefix, name);
+        mailboxes.push(Mailbox {
+            account_id: account_id.to_string(),
+            account_name: account_name.to_string(),
+            name: name.clone(),
+            path: entry.path(),
+        });
+        collect_mailboxes(
+            &entry.path(),
+            &format!("{}/", name),
+            account_id,
+            account_name,
+            mailboxes,
+        );
+    }
+}
+
+// 简单的通配符匹配，支持 * 与 ?，不区分大小写
+pub fn pattern_matches(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+    let (mut p, mut t) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, t));
+            p += 1;
+        } else if let Some((star_p, star_t)) = star {
+            p = star_p + 1;
+            t = star_t + 1;
+            star = Some((star_p, star_t + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+// 判断邮箱是否在监听范围内：账户未被禁用、名称匹配 include 且不匹配 exclude
+// mail_accounts 的键可以是账户名称或 UUID，未列出的账户默认启用
+pub fn mailbox_selected(
+    mailbox: &Mailbox,
+    include: &[String],
+    exclude: &[String],
+    accounts: &HashMap<String, bool>,
+) -> bool {
+    let account_enabled = accounts
+        .get(&mailbox.account_name)
+        .or_else(|| accounts.get(&mailbox.account_id))
+        .copied()
+        .unwrap_or(true);
+    account_enabled
+        && include.iter().any(|p| pattern_matches(p, &mailbox.name))
+        && !exclude.iter().any(|p| pattern_matches(p, &mailbox.name))
+}
+
+// 邮件文件所属的邮箱目录，即路径中最深的 .mbox 目录
+pub fn owning_mailbox(path: &Path) -> Option<PathBuf> {
+    path.ancestors()
+        .find(|p| p.extension().map(|ext| ext == "mbox").unwrap_or(false))
+        .map(Path::to_path_buf)
+}
+
+// 需要订阅的目录：已被选中的祖先邮箱会递归覆盖子邮箱，无需重复订阅
+pub fn watch_roots(selected: &[Mailbox]) -> Vec<PathBuf> {
+    let mut roots: Vec<PathBuf> = Vec::new();
+    for mailbox in selected {
+        if !roots.iter().any(|root| mailbox.path.starts_with(root)) {
+            roots.push(mailbox.path.clone());
+        }
+    }
+    roots
+}
@@ -10,7 +10,8 @@ fn main() {
     if args.len() > 1 {
         let arg1 = args[1].to_lowercase();
         if arg1.starts_with(ARGS_APP) {
-            return float_window::main(&args[2], &args[3]).unwrap();
+            let expires_at = args.get(4).and_then(|t| t.parse().ok());
+            return float_window::main(&args[2], &args[3], expires_at).unwrap();
         }
     }
 
@@ -0,0 +1,155 @@
+use std::{
+    collections::HashMap,
+    error::Error,
+    sync::mpsc::{self, Receiver, RecvTimeoutError},
+    thread,
+    time::Duration,
+};
+
+use zbus::{
+    blocking::{connection, fdo::MonitoringProxy, Connection, MessageIterator},
+    zvariant::OwnedValue,
+    MatchRule, Message, MessageType,
+};
+
+use crate::{
+    catch_up::unix_now,
+    desktop_notification::NOTIFICATION_APP_NAME,
+    source::{IncomingMessage, MessageSource, NOTIFICATION_SOURCE},
+    supervisor::StopSignal,
+};
+
+const NOTIFICATIONS_INTERFACE: &str = "org.freedesktop.Notifications";
+
+// org.freedesktop.Notifications.Notify 的参数，签名为 susssasa{sv}i
+type NotifyArgs = (
+    String,
+    u32,
+    String,
+    String,
+    String,
+    Vec<String>,
+    HashMap<String, OwnedValue>,
+    i32,
+);
+
+// 应用名或 desktop-entry 提示在白名单中（忽略大小写），"*" 表示除 MessAuto 自己以外的所有应用
+pub fn app_allowed(allowlist: &[String], app_name: &str, desktop_entry: Option<&str>) -> bool {
+    // 自己显示的验证码通知不能再被当作新信息，否则使用 "*" 时会重复投递
+    if app_name == NOTIFICATION_APP_NAME {
+        return false;
+    }
+    allowlist.iter().any(|allowed| {
+        allowed == "*"
+            || allowed.eq_ignore_ascii_case(app_name)
+            || desktop_entry.is_some_and(|entry| allowed.eq_ignore_ascii_case(entry))
+    })
+}
+
+// 通知正文允许 <b>、<i>、<a> 等简单标记，去掉标签并还原实体，保留原有空白
+pub fn strip_markup(text: &str) -> String {
+    let mut plain = String::with_capacity(text.len());
+    let mut in_tag = false;
+    for c in text.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => in_tag = false,
+            c if !in_tag => plain.push(c),
+            _ => {}
+        }
+    }
+    plain
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+// 把一次 Notify 调用转换为统一信息，标题和正文一起参与检测
+fn parse_notify(message: &Message, allowlist: &[String]) -> Option<IncomingMessage> {
+    let header = message.header();
+    if header.member()?.as_str() != "Notify" {
+        return None;
+    }
+    let body = message.body();
+    let (app_name, _, _, summary, text, _, hints, _): NotifyArgs = body.deserialize().ok()?;
+    let desktop_entry = hints
+        .get("desktop-entry")
+        .and_then(|entry| entry.downcast_ref::<String>().ok());
+    if !app_allowed(allowlist, &app_name, desktop_entry.as_deref()) {
+        return None;
+    }
+    let text = strip_markup(&text);
+    Some(IncomingMessage {
+        source: NOTIFICATION_SOURCE.to_string(),
+        sender: Some(app_name),
+        body: format!("{}\n{}", summary, text).trim().to_string(),
+        subject: Some(summary),
+        received_at: unix_now(),
+        id: None,
+        origin: None,
+    })
+}
+
+// 桌面通知信息源：以监视器身份旁听会话总线上发往通知服务的 Notify 调用
+pub struct NotificationSource {
+    connection: Connection,
+    rx: Receiver<IncomingMessage>,
+}
+
+impl NotificationSource {
+    // address 为空时连接当前用户的会话总线
+    pub fn new(address: Option<&str>, allowlist: Vec<String>) -> zbus::Result<Self> {
+        let connection = match address {
+            Some(address) => connection::Builder::address(address)?.build()?,
+            None => Connection::session()?,
+        };
+        // 成为监视器之前创建迭代器，避免漏掉第一条通知
+        let iterator = MessageIterator::from(&connection);
+        let rule = MatchRule::builder()
+            .msg_type(MessageType::MethodCall)
+            .interface(NOTIFICATIONS_INTERFACE)?
+            .member("Notify")?
+            .build();
+        // 成为监视器后连接只能接收，不能再发送任何消息
+        MonitoringProxy::new(&connection)?.become_monitor(&[rule], 0)?;
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            for message in iterator {
+                let Ok(message) = message else {
+                    break;
+                };
+                if let Some(notification) = parse_notify(&message, &allowlist) {
+                    if tx.send(notification).is_err() {
+                        break;
+                    }
+                }
+            }
+        });
+        Ok(NotificationSource { connection, rx })
+    }
+}
+
+impl MessageSource for NotificationSource {
+    fn name(&self) -> String {
+        NOTIFICATION_SOURCE.to_string()
+    }
+
+    fn next_messages(&mut self, stop: &StopSignal) -> Result<Vec<IncomingMessage>, Box<dyn Error>> {
+        while !stop.is_stopped() {
+            match self.rx.recv_timeout(Duration::from_millis(500)) {
+                Ok(message) => return Ok(vec![message]),
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => return Err("D-Bus connection closed".into()),
+            }
+        }
+        Ok(Vec::new())
+    }
+}
+
+impl Drop for NotificationSource {
+    fn drop(&mut self) {
+        let _ = self.connection.clone().close();
+    }
+}
@@ -0,0 +1,98 @@
+use log::info;
+use rust_i18n::t;
+
+use crate::{
+    catch_up::CatchUpAction,
+    check_captcha_or_other,
+    delivery::{deliver, show_only, sinks_for, DeliveryContext, SinkConfig},
+    get_captchas, get_real_captcha,
+    housekeeping::schedule_housekeeping,
+    read_config,
+    source::IncomingMessage,
+    MAConfig,
+};
+
+// 过长的邮件正文通常是营销邮件，不做检测；短信不受限制，中文短信很容易超过这个字节数
+const MAX_BODY_LEN: usize = 500;
+
+// 一条信息经过检测与过滤后的处理方式
+#[derive(Debug, Clone, PartialEq)]
+pub enum Decision {
+    // 不含验证码
+    Ignore,
+    // 按补处理策略跳过
+    Skip,
+    // 窗口期内已投递过
+    Duplicate,
+    // 只在浮动窗口中显示
+    Show(String),
+    // 复制到剪贴板，并按配置粘贴、回车
+    Deliver(String),
+}
+
+// 检测 → 过滤：所有信息源共用同一套规则
+pub fn evaluate(message: &IncomingMessage, config: &MAConfig) -> Decision {
+    if message.is_mail() && message.body.len() >= MAX_BODY_LEN {
+        return Decision::Ignore;
+    }
+    if !check_captcha_or_other(&message.body, &config.flags) {
+        return Decision::Ignore;
+    }
+    if message.is_mail() {
+        info!("{}", t!("new-verification-email-detected"));
+    } else {
+        info!("{}", t!("new-verification-code-detected"));
+    }
+    info!(
+        "{}:{:?}",
+        t!("all-possible-codes"),
+        get_captchas(&message.body)
+    );
+    let code = get_real_captcha(&message.body);
+    info!("{}:{:?}", t!("real-verification-code"), code);
+
+    let action = config.catch_up_action(message.received_at);
+    if action == CatchUpAction::Skip {
+        info!("{}", t!("catch-up-skipped"));
+        return Decision::Skip;
+    }
+    if config.is_duplicate(&code, message.sender.as_deref(), message.id.as_deref()) {
+        // 重复的验证码已在 is_duplicate 中以 debug 级别记录
+        return Decision::Duplicate;
+    }
+    if action == CatchUpAction::ShowOnly {
+        info!("{}", t!("catch-up-show-only"));
+        return Decision::Show(code);
+    }
+    if config.float_window {
+        return Decision::Show(code);
+    }
+    Decision::Deliver(code)
+}
+
+// 检测 → 过滤后得到验证码和要使用的投递方式，不需要投递时为空
+pub fn plan(message: &IncomingMessage, config: &MAConfig) -> Option<(String, Vec<SinkConfig>)> {
+    match evaluate(message, config) {
+        // 只显示时用浮动窗口代替剪贴板、粘贴等交互式投递方式
+        Decision::Show(code) => Some((code, show_only(sinks_for(message, config)))),
+        Decision::Deliver(code) => Some((code, sinks_for(message, config))),
+        _ => None,
+    }
+}
+
+// 检测 → 过滤 → 投递，验证码交给用户后按规则整理邮箱
+pub fn process_message(message: IncomingMessage) {
+    if message.is_mail() {
+        info!("{}: {}", t!("new-email-received"), message.source);
+    }
+    let config = read_config();
+    let Some((code, sinks)) = plan(&message, &config) else {
+        return;
+    };
+    let sinks: Vec<_> = sinks.iter().map(SinkConfig::build).collect();
+    let report = deliver(&sinks, DeliveryContext::new(&code, &message, &config));
+    // 只有验证码写入剪贴板或输入到前台应用后才整理邮箱，只显示或交给外部集成都不算
+    if let (true, Some(origin)) = (report.handed_over, &message.origin) {
+        schedule_housekeeping(origin, message.sender.as_deref());
+    }
+}
@@ -0,0 +1,177 @@
+use std::{
+    error::Error,
+    fs::{self, DirBuilder},
+    io::{self, BufRead, BufReader, Read, Write},
+    os::unix::{
+        fs::{DirBuilderExt, FileTypeExt, PermissionsExt},
+        net::{UnixListener, UnixStream},
+    },
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Receiver, Sender},
+    thread,
+    time::Duration,
+};
+
+use log::info;
+use rust_i18n::t;
+use serde::Deserialize;
+
+use crate::{
+    catch_up::unix_now,
+    source::{IncomingMessage, MessageSource, SOCKET_SOURCE},
+    supervisor::StopSignal,
+};
+
+// 单行 JSON 的长度上限
+const MAX_LINE_LEN: u64 = 64 * 1024;
+
+// 脚本写入的一行 JSON，只有 body 是必需的
+#[derive(Deserialize, Debug)]
+struct SocketPayload {
+    #[serde(alias = "text")]
+    body: String,
+    #[serde(default)]
+    sender: Option<String>,
+    // 可选标签，例如 "cron"，信息源名称记为 "socket:cron"
+    #[serde(default)]
+    source: Option<String>,
+    #[serde(default)]
+    subject: Option<String>,
+    #[serde(default)]
+    timestamp: Option<i64>,
+    #[serde(default)]
+    id: Option<String>,
+}
+
+pub fn parse_line(line: &str) -> Result<IncomingMessage, serde_json::Error> {
+    let payload: SocketPayload = serde_json::from_str(line)?;
+    let source = match payload.source.filter(|label| !label.is_empty()) {
+        Some(label) => format!("{}:{}", SOCKET_SOURCE, label),
+        None => SOCKET_SOURCE.to_string(),
+    };
+    Ok(IncomingMessage {
+        source,
+        sender: payload.sender,
+        subject: payload.subject,
+        body: payload.body,
+        received_at: payload.timestamp.unwrap_or_else(unix_now),
+        id: payload.id,
+        origin: None,
+    })
+}
+
+// 先在权限为 0700 的临时目录中创建套接字并设为 0600，再移动到目标路径，
+// 其他用户在任何时刻都无法连接
+fn bind_private(path: &Path) -> io::Result<UnixListener> {
+    if let Ok(metadata) = fs::symlink_metadata(path) {
+        if !metadata.file_type().is_socket() {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("{} exists and is not a socket", path.display()),
+            ));
+        }
+        // 上次退出时残留的套接字
+        fs::remove_file(path)?;
+    }
+    let staging = path.with_extension(format!("{}.tmp", std::process::id()));
+    DirBuilder::new().mode(0o700).create(&staging)?;
+    let staged = staging.join("socket");
+    let result = UnixListener::bind(&staged).and_then(|listener| {
+        fs::set_permissions(&staged, fs::Permissions::from_mode(0o600))?;
+        fs::rename(&staged, path)?;
+        Ok(listener)
+    });
+    let _ = fs::remove_file(&staged);
+    let _ = fs::remove_dir(&staging);
+    result
+}
+
+// 逐行读取一个连接，每行回复 {"ok":true} 或错误原因
+fn serve(stream: UnixStream, tx: Sender<IncomingMessage>) {
+    let Ok(mut writer) = stream.try_clone() else {
+        return;
+    };
+    let mut reader = BufReader::new(stream);
+    loop {
+        let mut line = String::new();
+        match (&mut reader).take(MAX_LINE_LEN).read_line(&mut line) {
+            Ok(0) | Err(_) => return,
+            Ok(_) => {}
+        }
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let reply = match parse_line(line) {
+            Ok(message) => {
+                if tx.send(message).is_err() {
+                    return;
+                }
+                serde_json::json!({"ok": true})
+            }
+            Err(e) => serde_json::json!({"ok": false, "error": e.to_string()}),
+        };
+        if writeln!(writer, "{}", reply).is_err() {
+            return;
+        }
+    }
+}
+
+// Unix 套接字信息源：接收脚本写入的换行分隔 JSON，套接字只允许当前用户访问
+pub struct SocketSource {
+    listener: UnixListener,
+    path: PathBuf,
+    tx: Sender<IncomingMessage>,
+    rx: Receiver<IncomingMessage>,
+}
+
+impl SocketSource {
+    pub fn new(path: &Path) -> io::Result<Self> {
+        let listener = bind_private(path)?;
+        // 非阻塞 accept，便于及时响应停止请求
+        listener.set_nonblocking(true)?;
+        info!("{}: {}", t!("listening-to-socket"), path.display());
+        let (tx, rx) = mpsc::channel();
+        Ok(SocketSource {
+            listener,
+            path: path.to_path_buf(),
+            tx,
+            rx,
+        })
+    }
+}
+
+impl MessageSource for SocketSource {
+    fn name(&self) -> String {
+        SOCKET_SOURCE.to_string()
+    }
+
+    fn next_messages(&mut self, stop: &StopSignal) -> Result<Vec<IncomingMessage>, Box<dyn Error>> {
+        while !stop.is_stopped() {
+            loop {
+                match self.listener.accept() {
+                    Ok((stream, _)) => {
+                        stream.set_nonblocking(false)?;
+                        let tx = self.tx.clone();
+                        thread::spawn(move || serve(stream, tx));
+                    }
+                    Err(e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                    Err(e) => return Err(e.into()),
+                }
+            }
+            // 自身持有发送端，只会超时，不会断开
+            if let Ok(message) = self.rx.recv_timeout(Duration::from_millis(100)) {
+                let mut messages = vec![message];
+                messages.extend(self.rx.try_iter());
+                return Ok(messages);
+            }
+        }
+        Ok(Vec::new())
+    }
+}
+
+impl Drop for SocketSource {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
@@ -0,0 +1,81 @@
+use std::{error::Error, time::Duration};
+
+use log::warn;
+use rust_i18n::t;
+
+use crate::{housekeeping::MailOrigin, supervisor::StopSignal};
+
+// 短信、桌面通知、转发短信与脚本输入类信息源名称，其余信息源都是邮件
+pub const IMESSAGE_SOURCE: &str = "imessage";
+pub const KDECONNECT_SOURCE: &str = "kdeconnect";
+pub const NOTIFICATION_SOURCE: &str = "notification";
+pub const WEBHOOK_SOURCE: &str = "webhook";
+pub const SOCKET_SOURCE: &str = "socket";
+
+// 各个信息源产出的统一信息结构，交给同一条检测与投递流程处理
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct IncomingMessage {
+    // 信息源名称，例如 "imessage"、"mail"、"imap:Work"
+    pub source: String,
+    pub sender: Option<String>,
+    pub subject: Option<String>,
+    pub body: String,
+    // 接收时间（unix 秒）
+    pub received_at: i64,
+    // 信息源内的唯一标识，例如 iMessage guid 或邮件 Message-ID，用于去重
+    pub id: Option<String>,
+    // 可写信息源（IMAP、Maildir）中的位置，用于投递后的收尾操作
+    pub origin: Option<MailOrigin>,
+}
+
+impl IncomingMessage {
+    pub fn is_mail(&self) -> bool {
+        // 带标签的信息源名称形如 "socket:cron"，按冒号前的部分判断
+        let kind = self.source.split(':').next().unwrap_or_default();
+        !matches!(
+            kind,
+            IMESSAGE_SOURCE
+                | KDECONNECT_SOURCE
+                | NOTIFICATION_SOURCE
+                | WEBHOOK_SOURCE
+                | SOCKET_SOURCE
+        )
+    }
+}
+
+// 新的信息源只需实现此 trait，检测、去重与投递由 run_source 统一处理
+pub trait MessageSource: Send {
+    fn name(&self) -> String;
+
+    // 阻塞直到有新信息；收到停止请求时返回空列表，出错时由调用方退避后重试
+    fn next_messages(&mut self, stop: &StopSignal) -> Result<Vec<IncomingMessage>, Box<dyn Error>>;
+}
+
+// 持续读取信息源并把每条信息交给 handle，出错后按指数退避重试
+pub fn run_source(
+    source: &mut dyn MessageSource,
+    stop: &StopSignal,
+    handle: &mut dyn FnMut(IncomingMessage),
+) {
+    let mut backoff = Duration::from_secs(5);
+    while !stop.is_stopped() {
+        match source.next_messages(stop) {
+            Ok(messages) => {
+                backoff = Duration::from_secs(5);
+                for message in messages {
+                    if stop.is_stopped() {
+                        return;
+                    }
+                    handle(message);
+                }
+            }
+            Err(e) => {
+                warn!("{}: {}: {}", t!("source-error"), source.name(), e);
+                if !stop.sleep(backoff) {
+                    return;
+                }
+                backoff = (backoff * 2).min(Duration::from_secs(300));
+            }
+        }
+    }
+}
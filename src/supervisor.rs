@@ -0,0 +1,153 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use log::info;
+use rust_i18n::t;
+
+// 通知监听线程退出，线程需要定期检查
+#[derive(Clone, Debug, Default)]
+pub struct StopSignal(Arc<AtomicBool>);
+
+impl StopSignal {
+    pub fn new() -> Self {
+        StopSignal::default()
+    }
+
+    pub fn stop(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+
+    // 可被停止打断的休眠，返回 false 表示已收到停止请求
+    pub fn sleep(&self, duration: Duration) -> bool {
+        let deadline = Instant::now() + duration;
+        while Instant::now() < deadline {
+            if self.is_stopped() {
+                return false;
+            }
+            thread::sleep((deadline - Instant::now()).min(Duration::from_millis(200)));
+        }
+        !self.is_stopped()
+    }
+}
+
+struct Worker {
+    // 启动时使用的配置摘要，配置变化时据此决定是否重启
+    key: String,
+    stop: StopSignal,
+    handle: JoinHandle<()>,
+}
+
+// 管理各个信息源的监听线程，保证每个信息源同时最多只有一个实例
+#[derive(Default)]
+pub struct Supervisor {
+    workers: Mutex<HashMap<String, Worker>>,
+    // 已发出停止请求、可能尚未退出的线程，同名信息源再次启动前需要等待它们退出
+    stopping: Mutex<HashMap<String, JoinHandle<()>>>,
+}
+
+impl Supervisor {
+    pub fn new() -> Self {
+        Supervisor::default()
+    }
+
+    // 确保名为 name 的信息源以 key 对应的配置运行：未运行则启动，配置变化则重启。
+    // 不等待旧实例退出，新线程会先等旧线程退出再运行，调用方（托盘菜单）不会被阻塞
+    pub fn ensure<F>(&self, name: &str, key: &str, run: F)
+    where
+        F: FnOnce(StopSignal) + Send + 'static,
+    {
+        let mut workers = self.workers.lock().unwrap();
+        let previous = match workers.remove(name) {
+            Some(worker) if worker.key == key && !worker.handle.is_finished() => {
+                workers.insert(name.to_string(), worker);
+                return;
+            }
+            Some(worker) => {
+                info!("{}: {}", t!("source-restarting"), name);
+                worker.stop.stop();
+                Some(worker.handle)
+            }
+            None => {
+                info!("{}: {}", t!("source-starting"), name);
+                self.stopping.lock().unwrap().remove(name)
+            }
+        };
+        let stop = StopSignal::new();
+        let signal = stop.clone();
+        let handle = thread::spawn(move || {
+            if let Some(previous) = previous {
+                let _ = previous.join();
+            }
+            if !signal.is_stopped() {
+                run(signal);
+            }
+        });
+        workers.insert(
+            name.to_string(),
+            Worker {
+                key: key.to_string(),
+                stop,
+                handle,
+            },
+        );
+    }
+
+    // 发出停止请求后立即返回，线程在下一次检查停止请求时退出
+    pub fn stop(&self, name: &str) {
+        let worker = self.workers.lock().unwrap().remove(name);
+        if let Some(worker) = worker {
+            info!("{}: {}", t!("source-stopping"), name);
+            worker.stop.stop();
+            let mut stopping = self.stopping.lock().unwrap();
+            stopping.retain(|_, handle| !handle.is_finished());
+            stopping.insert(name.to_string(), worker.handle);
+        }
+    }
+
+    // 停止名称以 prefix 开头、但不在 keep 中的信息源
+    pub fn retain(&self, prefix: &str, keep: &[String]) {
+        let names: Vec<String> = self
+            .workers
+            .lock()
+            .unwrap()
+            .keys()
+            .filter(|name| name.starts_with(prefix) && !keep.contains(name))
+            .cloned()
+            .collect();
+        for name in names {
+            self.stop(&name);
+        }
+    }
+
+    pub fn is_running(&self, name: &str) -> bool {
+        self.workers
+            .lock()
+            .unwrap()
+            .get(name)
+            .is_some_and(|worker| !worker.handle.is_finished())
+    }
+
+    pub fn running(&self) -> Vec<String> {
+        let mut names: Vec<String> = self
+            .workers
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(_, worker)| !worker.handle.is_finished())
+            .map(|(name, _)| name.clone())
+            .collect();
+        names.sort();
+        names
+    }
+}
@@ -7,17 +7,17 @@ use rust_i18n::t;
 use simplelog::{
     ColorChoice, CombinedLogger, ConfigBuilder, LevelFilter, TermLogger, TerminalMode, WriteLogger,
 };
-use tao::platform::macos::ActivationPolicy;
-use tao::{
-    event_loop::{ControlFlow, EventLoopBuilder},
-    platform::macos::EventLoopExtMacOS,
-};
+use tao::event_loop::{ControlFlow, EventLoopBuilder};
+#[cfg(target_os = "macos")]
+use tao::platform::macos::{ActivationPolicy, EventLoopExtMacOS};
 use tray_icon::{menu::MenuEvent, TrayIconEvent};
 
+use MessAuto::catch_up::wake_monitor_thread;
+use MessAuto::housekeeping::drop_pending_housekeeping;
 use MessAuto::{
     auto_launch, check_accessibility, check_accessibility_with_no_action, check_full_disk_access,
-    config_path, get_sys_locale, log_path, mail_thread, messages_thread, read_config, TrayIcon,
-    TrayMenu, TrayMenuItems,
+    config_path, config_watch_thread, get_sys_locale, log_path, messages_thread, read_config,
+    sync_sources, TrayIcon, TrayMenu, TrayMenuItems,
 };
 
 rust_i18n::i18n!("locales");
@@ -49,17 +49,21 @@ pub fn main() {
 
     check_full_disk_access();
 
+    #[cfg(target_os = "macos")]
     let mut event_loop = EventLoopBuilder::new().build();
+    #[cfg(not(target_os = "macos"))]
+    let event_loop = EventLoopBuilder::new().build();
 
+    #[cfg(target_os = "macos")]
     event_loop.set_activation_policy(ActivationPolicy::Accessory);
     let auto = auto_launch();
 
     let mut config = read_config();
 
+    wake_monitor_thread();
     messages_thread();
-    if config.listening_to_mail {
-        mail_thread();
-    }
+    sync_sources(&config);
+    config_watch_thread();
 
     // 禁用自动更新
     // let (tx, rx) = mpsc::channel();
@@ -140,6 +144,7 @@ pub fn main() {
         // }
         if let Ok(event) = menu_channel.try_recv() {
             if event.id == tray_menu_items.quit_i.id() {
+                drop_pending_housekeeping();
                 tray_icon.take();
                 *control_flow = ControlFlow::Exit;
             } else if event.id == tray_menu_items.check_hide_icon_for_now.id() {
@@ -216,13 +221,13 @@ pub fn main() {
             } else if event.id == tray_menu_items.listening_to_mail.id() {
                 if tray_menu_items.listening_to_mail.is_checked() {
                     config.listening_to_mail = true;
-                    mail_thread();
                     info!("{}", t!("mail-listening-enabled"));
                 } else {
                     config.listening_to_mail = false;
                     info!("{}", t!("mail-listening-disabled"));
                 }
                 config.update().expect("failed to update config");
+                sync_sources(&config);
             } else if event.id == tray_menu_items.float_window.id() {
                 if tray_menu_items.float_window.is_checked() {
                     config.float_window = true;
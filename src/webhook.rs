@@ -0,0 +1,426 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    error::Error,
+    io::{self, BufRead, BufReader, Read, Write},
+    net::{TcpListener, TcpStream},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc::{channel, Receiver, Sender},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use hmac::{Hmac, Mac};
+use log::{info, warn};
+use percent_encoding::percent_decode_str;
+use rust_i18n::t;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::Sha256;
+
+use crate::{
+    catch_up::unix_now,
+    read_secret,
+    source::{IncomingMessage, MessageSource, WEBHOOK_SOURCE},
+    supervisor::StopSignal,
+};
+
+// 请求头：对请求体计算的 HMAC-SHA256，格式为 "sha256=<hex>"
+pub const SIGNATURE_HEADER: &str = "x-messauto-signature";
+// 请求头：直接携带共享密钥，供只能设置固定请求头的转发应用使用
+pub const SECRET_HEADER: &str = "x-messauto-secret";
+// secrets.json 中共享密钥的键名
+const SECRET_NAME: &str = "webhook";
+const MAX_BODY_LEN: usize = 64 * 1024;
+// 读取整个请求的总时限，发送缓慢的客户端不能一直占用连接
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+// 同时处理的连接数上限，超出的连接不读取请求，直接返回 429
+pub const MAX_CONNECTIONS: usize = 8;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct WebhookConfig {
+    // 监听地址，默认只接受本机连接
+    #[serde(default = "default_webhook_address")]
+    pub address: String,
+    // 未设置或为空字符串时从 secrets.json 中按 "webhook" 读取
+    #[serde(default)]
+    pub secret: Option<String>,
+    // 每分钟最多接受的请求数，认证失败的请求也计入
+    #[serde(default = "default_rate_limit")]
+    pub rate_limit: usize,
+}
+
+fn default_webhook_address() -> String {
+    "127.0.0.1:8787".to_string()
+}
+
+fn default_rate_limit() -> usize {
+    30
+}
+
+impl Default for WebhookConfig {
+    fn default() -> Self {
+        WebhookConfig {
+            address: default_webhook_address(),
+            secret: None,
+            rate_limit: default_rate_limit(),
+        }
+    }
+}
+
+impl WebhookConfig {
+    // 空密钥视为未设置，否则带空 SECRET_HEADER 的请求都能通过认证
+    pub fn secret(&self) -> Option<String> {
+        self.secret
+            .clone()
+            .filter(|secret| !secret.is_empty())
+            .or_else(|| read_secret(SECRET_NAME))
+            .filter(|secret| !secret.is_empty())
+    }
+}
+
+fn hmac(secret: &str, body: &[u8]) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+    mac.update(body);
+    mac
+}
+
+// 计算 SIGNATURE_HEADER 的值，投递到外部 webhook 时也使用同样的格式
+pub fn sign(secret: &str, body: &[u8]) -> String {
+    format!(
+        "sha256={}",
+        hex::encode(hmac(secret, body).finalize().into_bytes())
+    )
+}
+
+// 以常数时间比较签名或共享密钥，两个请求头都没有或密钥为空时认证失败
+pub fn authenticate(secret: &str, headers: &HashMap<String, String>, body: &[u8]) -> bool {
+    if secret.is_empty() {
+        return false;
+    }
+    if let Some(signature) = headers.get(SIGNATURE_HEADER) {
+        let signature = signature.trim();
+        let signature = signature.strip_prefix("sha256=").unwrap_or(signature);
+        let Ok(signature) = hex::decode(signature) else {
+            return false;
+        };
+        return hmac(secret, body).verify_slice(&signature).is_ok();
+    }
+    if let Some(provided) = headers.get(SECRET_HEADER) {
+        return constant_time_eq(provided.as_bytes(), secret.as_bytes());
+    }
+    false
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+// 解析 JSON 或表单请求体，字段名兼容常见短信转发应用的写法
+pub fn parse_payload(content_type: &str, body: &[u8]) -> Option<IncomingMessage> {
+    let fields: HashMap<String, String> = if content_type.starts_with("application/json") {
+        let Value::Object(object) = serde_json::from_slice(body).ok()? else {
+            return None;
+        };
+        object
+            .into_iter()
+            .filter_map(|(key, value)| match value {
+                Value::String(s) => Some((key, s)),
+                Value::Number(n) => Some((key, n.to_string())),
+                _ => None,
+            })
+            .collect()
+    } else if content_type.starts_with("application/x-www-form-urlencoded") {
+        let body = std::str::from_utf8(body).ok()?;
+        body.split('&')
+            .filter_map(|pair| pair.split_once('='))
+            .map(|(key, value)| (form_decode(key), form_decode(value)))
+            .collect()
+    } else {
+        return None;
+    };
+    let field = |names: &[&str]| names.iter().find_map(|name| fields.get(*name).cloned());
+    let body = field(&["body", "text", "content", "message"])?;
+    let received_at = field(&["timestamp", "time", "date"])
+        .and_then(|ts| ts.parse::<i64>().ok())
+        // 毫秒时间戳转为秒
+        .map(|ts| if ts > 100_000_000_000 { ts / 1000 } else { ts })
+        .unwrap_or_else(unix_now);
+    Some(IncomingMessage {
+        source: WEBHOOK_SOURCE.to_string(),
+        sender: field(&["sender", "from", "phone"]),
+        subject: None,
+        body,
+        received_at,
+        id: field(&["id"]),
+        origin: None,
+    })
+}
+
+fn form_decode(s: &str) -> String {
+    percent_decode_str(&s.replace('+', " "))
+        .decode_utf8_lossy()
+        .to_string()
+}
+
+// 滑动窗口限流：统计最近一分钟内的请求数
+pub struct RateLimiter {
+    limit: usize,
+    window: Duration,
+    recent: VecDeque<Instant>,
+}
+
+impl RateLimiter {
+    pub fn new(limit: usize, window: Duration) -> Self {
+        RateLimiter {
+            limit,
+            window,
+            recent: VecDeque::new(),
+        }
+    }
+
+    pub fn allow(&mut self, now: Instant) -> bool {
+        while self
+            .recent
+            .front()
+            .is_some_and(|t| now.duration_since(*t) >= self.window)
+        {
+            self.recent.pop_front();
+        }
+        if self.recent.len() >= self.limit {
+            return false;
+        }
+        self.recent.push_back(now);
+        true
+    }
+}
+
+struct Request {
+    method: String,
+    headers: HashMap<String, String>,
+    body: Vec<u8>,
+}
+
+// 每次读取前把超时设为剩余时间，超过总时限后不再读取
+struct DeadlineReader<'a> {
+    stream: &'a TcpStream,
+    deadline: Instant,
+}
+
+impl Read for DeadlineReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let remaining = self.deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Err(io::ErrorKind::TimedOut.into());
+        }
+        self.stream.set_read_timeout(Some(remaining))?;
+        self.stream.read(buf)
+    }
+}
+
+fn read_request(stream: &TcpStream) -> io::Result<Result<Request, u16>> {
+    let reader = DeadlineReader {
+        stream,
+        deadline: Instant::now() + REQUEST_TIMEOUT,
+    };
+    let mut reader = BufReader::new(reader.take(MAX_BODY_LEN as u64 + 8 * 1024));
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let Some(method) = request_line.split_whitespace().next() else {
+        return Ok(Err(400));
+    };
+    let mut headers = HashMap::new();
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(Err(400));
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            headers.insert(name.trim().to_ascii_lowercase(), value.trim().to_string());
+        }
+    }
+    let content_length: usize = match headers.get("content-length").map(|l| l.parse()) {
+        Some(Ok(length)) => length,
+        Some(Err(_)) => return Ok(Err(400)),
+        None => 0,
+    };
+    if content_length > MAX_BODY_LEN {
+        return Ok(Err(413));
+    }
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body)?;
+    Ok(Ok(Request {
+        method: method.to_string(),
+        headers,
+        body,
+    }))
+}
+
+fn respond(mut stream: &TcpStream, status: u16) {
+    let reason = match status {
+        202 => "Accepted",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        405 => "Method Not Allowed",
+        413 => "Payload Too Large",
+        429 => "Too Many Requests",
+        _ => "Internal Server Error",
+    };
+    let body = format!("{{\"status\":{}}}", status);
+    let _ = write!(
+        stream,
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        reason,
+        body.len(),
+        body
+    );
+}
+
+// 处理一个连接，认证通过且请求体有效时返回短信
+fn handle(
+    stream: TcpStream,
+    secret: &str,
+    limiter: &Mutex<RateLimiter>,
+) -> io::Result<Option<IncomingMessage>> {
+    stream.set_nonblocking(false)?;
+    let request = match read_request(&stream)? {
+        Ok(request) => request,
+        Err(status) => {
+            respond(&stream, status);
+            return Ok(None);
+        }
+    };
+    // 先读完请求再拒绝，否则未读数据会使连接被重置，客户端收不到 429
+    if !limiter.lock().unwrap().allow(Instant::now()) {
+        warn!("{}", t!("webhook-rate-limited"));
+        respond(&stream, 429);
+        return Ok(None);
+    }
+    if request.method != "POST" {
+        respond(&stream, 405);
+        return Ok(None);
+    }
+    if !authenticate(secret, &request.headers, &request.body) {
+        warn!("{}", t!("webhook-unauthorized"));
+        respond(&stream, 401);
+        return Ok(None);
+    }
+    let content_type = request
+        .headers
+        .get("content-type")
+        .map(|c| c.to_ascii_lowercase())
+        .unwrap_or_default();
+    match parse_payload(&content_type, &request.body) {
+        Some(message) => {
+            respond(&stream, 202);
+            Ok(Some(message))
+        }
+        None => {
+            respond(&stream, 400);
+            Ok(None)
+        }
+    }
+}
+
+// 占用一个连接名额，连接线程结束时释放
+struct ConnectionSlot(Arc<AtomicUsize>);
+
+impl ConnectionSlot {
+    fn acquire(active: &Arc<AtomicUsize>) -> Option<Self> {
+        if active.fetch_add(1, Ordering::SeqCst) >= MAX_CONNECTIONS {
+            active.fetch_sub(1, Ordering::SeqCst);
+            return None;
+        }
+        Some(ConnectionSlot(active.clone()))
+    }
+}
+
+impl Drop for ConnectionSlot {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+// 本地 HTTP 信息源：接收短信转发应用 POST 过来的短信
+pub struct WebhookSource {
+    listener: TcpListener,
+    secret: Arc<String>,
+    limiter: Arc<Mutex<RateLimiter>>,
+    // 正在处理的连接数
+    active: Arc<AtomicUsize>,
+    // 各连接线程解析出的短信
+    tx: Sender<IncomingMessage>,
+    rx: Receiver<IncomingMessage>,
+}
+
+impl WebhookSource {
+    pub fn new(config: &WebhookConfig, secret: String) -> io::Result<Self> {
+        let listener = TcpListener::bind(&config.address)?;
+        // 非阻塞 accept，便于及时响应停止请求
+        listener.set_nonblocking(true)?;
+        info!("{}: {}", t!("listening-to-webhook"), listener.local_addr()?);
+        let (tx, rx) = channel();
+        Ok(WebhookSource {
+            listener,
+            secret: Arc::new(secret),
+            limiter: Arc::new(Mutex::new(RateLimiter::new(
+                config.rate_limit,
+                Duration::from_secs(60),
+            ))),
+            active: Arc::new(AtomicUsize::new(0)),
+            tx,
+            rx,
+        })
+    }
+
+    pub fn local_port(&self) -> io::Result<u16> {
+        Ok(self.listener.local_addr()?.port())
+    }
+}
+
+impl MessageSource for WebhookSource {
+    fn name(&self) -> String {
+        WEBHOOK_SOURCE.to_string()
+    }
+
+    fn next_messages(&mut self, stop: &StopSignal) -> Result<Vec<IncomingMessage>, Box<dyn Error>> {
+        while !stop.is_stopped() {
+            match self.listener.accept() {
+                Ok((stream, _)) => {
+                    let Some(slot) = ConnectionSlot::acquire(&self.active) else {
+                        warn!("{}", t!("webhook-too-many-connections"));
+                        respond(&stream, 429);
+                        continue;
+                    };
+                    // 每个连接在单独的线程中读取，慢速客户端不会阻塞其他请求；单个连接出错不影响监听
+                    let secret = self.secret.clone();
+                    let limiter = self.limiter.clone();
+                    let tx = self.tx.clone();
+                    thread::spawn(move || {
+                        let _slot = slot;
+                        if let Ok(Some(message)) = handle(stream, &secret, &limiter) {
+                            let _ = tx.send(message);
+                        }
+                    });
+                }
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                    // 没有新连接时等待连接线程的结果
+                    if let Ok(message) = self.rx.recv_timeout(Duration::from_millis(100)) {
+                        let mut messages = vec![message];
+                        messages.extend(self.rx.try_iter());
+                        return Ok(messages);
+                    }
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Ok(Vec::new())
+    }
+}
@@ -0,0 +1,186 @@
+use std::time::{Duration, Instant};
+
+use MessAuto::{
+    clipboard::{
+        clear_deadline, concealed_text, restore_if_owned, ClipboardContents, ClipboardMark,
+    },
+    clipboard_snapshot::{
+        fits_single_request, should_capture_pasteboard_type, ClipboardSnapshot, SnapshotBudget,
+        SnapshotItem,
+    },
+    MAConfig,
+};
+
+#[test]
+fn test_clear_deadline() {
+    let delivered_at = 1714550400;
+    let config = MAConfig::default();
+    assert_eq!(
+        clear_deadline(&config, delivered_at, Some(delivered_at + 300)),
+        None
+    );
+
+    let config = MAConfig {
+        clear_clipboard_after: Some(30),
+        ..MAConfig::default()
+    };
+    assert_eq!(
+        clear_deadline(&config, delivered_at, Some(delivered_at + 300)),
+        Some(delivered_at + 30)
+    );
+
+    // 两者都适用时取较早的时间，没有有效期时只按秒数
+    let config = MAConfig {
+        clear_clipboard_after: Some(600),
+        clear_clipboard_at_expiry: true,
+        ..MAConfig::default()
+    };
+    assert_eq!(
+        clear_deadline(&config, delivered_at, Some(delivered_at + 300)),
+        Some(delivered_at + 300)
+    );
+    assert_eq!(
+        clear_deadline(&config, delivered_at, None),
+        Some(delivered_at + 600)
+    );
+
+    let config = MAConfig {
+        clear_clipboard_at_expiry: true,
+        ..MAConfig::default()
+    };
+    assert_eq!(clear_deadline(&config, delivered_at, None), None);
+}
+
+#[test]
+fn test_restore_skipped_when_clipboard_changed() {
+    // 剪贴板中不是本次的验证码时立即放弃恢复，不等待 delay
+    let code = format!("messauto-{}", std::process::id());
+    let contents = ClipboardContents::Basic(
+        Ok("old contents".to_string()),
+        Err(arboard::Error::ContentNotAvailable),
+    );
+    let started = Instant::now();
+    assert!(!restore_if_owned(
+        &ClipboardMark::new(&code),
+        contents,
+        started,
+        Duration::from_secs(5)
+    ));
+    assert!(started.elapsed() < Duration::from_secs(1));
+}
+
+#[test]
+fn test_snapshot_budget_skips_large_formats() {
+    let mut budget = SnapshotBudget::new(100);
+    assert!(budget.admit("text/plain", 60));
+    // 超出剩余额度的格式被跳过，较小的格式仍可保存
+    assert!(!budget.admit("image/png", 50));
+    assert!(budget.admit("text/html", 40));
+    assert!(!budget.admit("text/rtf", 1));
+
+    let snapshot = ClipboardSnapshot {
+        items: vec![
+            SnapshotItem {
+                formats: vec![("public.file-url".to_string(), b"file:///a".to_vec())],
+            },
+            SnapshotItem {
+                formats: vec![("public.file-url".to_string(), b"file:///b".to_vec())],
+            },
+        ],
+    };
+    assert!(!snapshot.is_empty());
+    assert_eq!(
+        snapshot.format_names(),
+        vec!["public.file-url", "public.file-url"]
+    );
+    assert!(ClipboardSnapshot {
+        items: vec![SnapshotItem::default()]
+    }
+    .is_empty());
+}
+
+#[test]
+fn test_pasteboard_type_filter() {
+    assert!(should_capture_pasteboard_type("public.utf8-plain-text"));
+    assert!(should_capture_pasteboard_type("public.png"));
+    assert!(should_capture_pasteboard_type("public.file-url"));
+    // 文件承诺和系统转换出的类型不读取
+    assert!(!should_capture_pasteboard_type(
+        "com.apple.pasteboard.promised-file-url"
+    ));
+    assert!(!should_capture_pasteboard_type(
+        "com.apple.NSFilePromiseItemMetaData"
+    ));
+    assert!(!should_capture_pasteboard_type(
+        "dyn.ah62d4rv4gu8y6y4grf0gn5xbrzw1gydcr7u1e3cytf2gn"
+    ));
+    assert!(!should_capture_pasteboard_type(
+        "CorePasteboardFlavorType 0x75726C20"
+    ));
+}
+
+#[test]
+fn test_large_formats_exceed_single_request() {
+    // 没有 BIG-REQUESTS 扩展时单个请求最大 256 KiB
+    let maximum_request_bytes = 262_140;
+    assert!(fits_single_request(
+        "text/html",
+        64 * 1024,
+        maximum_request_bytes
+    ));
+    assert!(!fits_single_request(
+        "image/png",
+        4 * 1024 * 1024,
+        maximum_request_bytes
+    ));
+    assert!(!fits_single_request(
+        "image/png",
+        maximum_request_bytes - 64,
+        maximum_request_bytes
+    ));
+}
+
+#[test]
+fn test_concealed_text_has_history_hints() {
+    let snapshot = concealed_text("482913");
+    assert_eq!(snapshot.items.len(), 1);
+    let formats = &snapshot.items[0].formats;
+    let format = |name: &str| {
+        formats
+            .iter()
+            .find(|(format, _)| format == name)
+            .map(|(_, data)| data.as_slice())
+    };
+    if cfg!(target_os = "macos") {
+        assert_eq!(format("public.utf8-plain-text"), Some(&b"482913"[..]));
+        assert!(format("org.nspasteboard.ConcealedType").is_some());
+        assert!(format("org.nspasteboard.TransientType").is_some());
+    } else {
+        assert_eq!(format("UTF8_STRING"), Some(&b"482913"[..]));
+        assert_eq!(format("STRING"), Some(&b"482913"[..]));
+        assert_eq!(format("x-kde-passwordManagerHint"), Some(&b"secret"[..]));
+    }
+    // 非 ASCII 文本不提供 Latin-1 的 STRING
+    let snapshot = concealed_text("验证码 482913");
+    assert!(!snapshot.format_names().contains(&"STRING"));
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+#[ignore = "requires an X server"]
+fn test_wait_until_released_gives_up_at_deadline() {
+    use MessAuto::clipboard_snapshot::{restore, wait_until_released};
+
+    let snapshot = ClipboardSnapshot {
+        items: vec![SnapshotItem {
+            formats: vec![("UTF8_STRING".to_string(), b"old contents".to_vec())],
+        }],
+    };
+    restore(&snapshot).unwrap();
+    // 没有其他程序写入剪贴板，到时间后不再等待
+    let start = Instant::now();
+    wait_until_released(start + Duration::from_millis(300));
+    let waited = start.elapsed();
+    assert!(waited >= Duration::from_millis(300));
+    assert!(waited < Duration::from_secs(2));
+}
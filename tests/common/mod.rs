@@ -0,0 +1,51 @@
+// 多个测试文件共用的辅助函数，每个测试文件只用到其中一部分
+#![allow(dead_code)]
+
+use std::{fs, path::PathBuf};
+#[cfg(target_os = "linux")]
+use std::{
+    io::{BufRead, BufReader},
+    process::{Child, Command, Stdio},
+};
+
+// 以测试名和进程号区分的空临时目录
+pub fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("messauto-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+// 私有会话总线，测试结束时关闭
+#[cfg(target_os = "linux")]
+pub struct PrivateBus {
+    daemon: Child,
+    pub address: String,
+}
+
+#[cfg(target_os = "linux")]
+impl Drop for PrivateBus {
+    fn drop(&mut self) {
+        let _ = self.daemon.kill();
+        let _ = self.daemon.wait();
+    }
+}
+
+// 启动私有的 dbus-daemon；用到它的测试标记为 #[ignore]，
+// 在装有 dbus-daemon 的环境中用 cargo test -- --include-ignored 运行
+#[cfg(target_os = "linux")]
+pub fn private_bus() -> PrivateBus {
+    let mut daemon = Command::new("dbus-daemon")
+        .args(["--session", "--nofork", "--print-address"])
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("dbus-daemon not available");
+    let mut address = String::new();
+    BufReader::new(daemon.stdout.take().unwrap())
+        .read_line(&mut address)
+        .expect("failed to read dbus-daemon address");
+    PrivateBus {
+        daemon,
+        address: address.trim().to_string(),
+    }
+}
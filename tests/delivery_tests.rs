@@ -0,0 +1,313 @@
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    error::Error,
+    io::{BufRead, BufReader, Read, Write},
+    net::TcpListener,
+    rc::Rc,
+    thread,
+    time::{Duration, Instant},
+};
+
+use MessAuto::{
+    catch_up::unix_now,
+    delivery::{
+        deliver, paste_target, sinks_for, DeliveryContext, DeliverySink, PasteFallback,
+        PasteTarget, SinkConfig, WebhookSink,
+    },
+    frontmost::{FrontmostApp, FrontmostAppProvider},
+    source::IncomingMessage,
+    webhook::sign,
+    MAConfig,
+};
+
+fn message(source: &str) -> IncomingMessage {
+    IncomingMessage {
+        source: source.to_string(),
+        sender: Some("+15550100".to_string()),
+        subject: None,
+        body: "Your code is 482913".to_string(),
+        received_at: unix_now(),
+        id: None,
+        origin: None,
+    }
+}
+
+// 记录调用顺序，按需返回失败
+struct FakeSink {
+    name: &'static str,
+    fail: bool,
+    calls: Rc<RefCell<Vec<String>>>,
+}
+
+impl DeliverySink for FakeSink {
+    fn name(&self) -> String {
+        self.name.to_string()
+    }
+
+    fn deliver(&self, ctx: &mut DeliveryContext) -> Result<(), Box<dyn Error>> {
+        self.calls
+            .borrow_mut()
+            .push(format!("{}:{}", self.name, ctx.code));
+        if self.fail {
+            return Err("unavailable".into());
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test_sinks_for_lookup_order() {
+    let mut delivery = HashMap::new();
+    delivery.insert("imap:Work".to_string(), vec![SinkConfig::Paste]);
+    delivery.insert("imap".to_string(), vec![SinkConfig::FloatWindow]);
+    delivery.insert("sms".to_string(), vec![SinkConfig::Clipboard]);
+    let config = MAConfig {
+        delivery,
+        ..MAConfig::default()
+    };
+    assert_eq!(
+        sinks_for(&message("imap:Work"), &config),
+        vec![SinkConfig::Paste]
+    );
+    assert_eq!(
+        sinks_for(&message("imap:Home"), &config),
+        vec![SinkConfig::FloatWindow]
+    );
+    assert_eq!(
+        sinks_for(&message("kdeconnect"), &config),
+        vec![SinkConfig::Clipboard]
+    );
+
+    // 未配置时沿用 auto_paste 开关
+    assert_eq!(
+        sinks_for(&message("mail"), &config),
+        vec![SinkConfig::Clipboard]
+    );
+    let config = MAConfig {
+        auto_paste: true,
+        ..config
+    };
+    assert_eq!(
+        sinks_for(&message("mail"), &config),
+        vec![SinkConfig::Paste]
+    );
+}
+
+#[test]
+fn test_sink_config_from_json() {
+    let sinks: Vec<SinkConfig> = serde_json::from_str(
+        r#"[{"type": "clipboard"}, {"type": "float_window"},
+            {"type": "webhook", "url": "http://127.0.0.1:9/hook"}]"#,
+    )
+    .unwrap();
+    assert_eq!(
+        sinks,
+        vec![
+            SinkConfig::Clipboard,
+            SinkConfig::FloatWindow,
+            SinkConfig::Webhook {
+                url: "http://127.0.0.1:9/hook".to_string(),
+                secret: None
+            }
+        ]
+    );
+}
+
+#[test]
+fn test_deliver_reports_each_sink() {
+    let calls = Rc::new(RefCell::new(Vec::new()));
+    let sink = |name, fail| -> Box<dyn DeliverySink> {
+        Box::new(FakeSink {
+            name,
+            fail,
+            calls: calls.clone(),
+        })
+    };
+    let sinks = vec![sink("first", true), sink("second", false)];
+    let config = MAConfig::default();
+    let message = message("mail");
+    let report = deliver(&sinks, DeliveryContext::new("482913", &message, &config));
+    // 前一个失败不影响后续投递
+    assert_eq!(*calls.borrow(), vec!["first:482913", "second:482913"]);
+    assert_eq!(report.results[0].0, "first");
+    assert!(report.results[0].1.is_err());
+    assert!(report.results[1].1.is_ok());
+    assert!(report.delivered());
+
+    let report = deliver(
+        &[sink("only", true)],
+        DeliveryContext::new("482913", &message, &config),
+    );
+    assert!(!report.delivered());
+}
+
+// 模拟键盘输入成功的投递方式
+struct TypingSink;
+
+impl DeliverySink for TypingSink {
+    fn name(&self) -> String {
+        "typing".to_string()
+    }
+
+    fn deliver(&self, ctx: &mut DeliveryContext) -> Result<(), Box<dyn Error>> {
+        ctx.mark_handed_over();
+        Ok(())
+    }
+}
+
+#[test]
+fn test_report_handed_over_only_for_interactive_sinks() {
+    let calls = Rc::new(RefCell::new(Vec::new()));
+    let config = MAConfig::default();
+    let message = message("mail");
+    // webhook、命令等外部集成成功不代表验证码已交给用户
+    let sinks: Vec<Box<dyn DeliverySink>> = vec![Box::new(FakeSink {
+        name: "external",
+        fail: false,
+        calls: calls.clone(),
+    })];
+    let report = deliver(&sinks, DeliveryContext::new("482913", &message, &config));
+    assert!(report.delivered());
+    assert!(!report.handed_over);
+
+    let sinks: Vec<Box<dyn DeliverySink>> = vec![
+        Box::new(FakeSink {
+            name: "external",
+            fail: false,
+            calls,
+        }),
+        Box::new(TypingSink),
+    ];
+    let report = deliver(&sinks, DeliveryContext::new("482913", &message, &config));
+    assert!(report.handed_over);
+}
+
+#[test]
+fn test_webhook_sink_posts_signed_payload() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let server = thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut headers = HashMap::new();
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            let line = line.trim_end();
+            if line.is_empty() {
+                break;
+            }
+            let (name, value) = line.split_once(": ").unwrap();
+            headers.insert(name.to_ascii_lowercase(), value.to_string());
+        }
+        let mut body = vec![0; headers["content-length"].parse().unwrap()];
+        reader.read_exact(&mut body).unwrap();
+        let mut out = stream;
+        out.write_all(b"HTTP/1.1 204 No Content\r\nConnection: close\r\n\r\n")
+            .unwrap();
+        (headers, body)
+    });
+
+    let config = MAConfig::default();
+    let message = message("webhook");
+    let sinks: Vec<Box<dyn DeliverySink>> = vec![Box::new(WebhookSink {
+        url: format!("http://127.0.0.1:{}/hook", port),
+        secret: Some("hook-secret".to_string()),
+    })];
+    let report = deliver(&sinks, DeliveryContext::new("482913", &message, &config));
+    assert!(report.delivered(), "{:?}", report);
+
+    let (headers, body) = server.join().unwrap();
+    assert_eq!(headers["x-messauto-signature"], sign("hook-secret", &body));
+    let payload: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(payload["code"], "482913");
+    assert_eq!(payload["sender"], "+15550100");
+    assert_eq!(payload["category"], "sms");
+}
+
+// 不响应的 webhook 地址不推迟后续的投递方式
+#[test]
+fn test_slow_webhook_does_not_block_later_sinks() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let server = thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        thread::sleep(Duration::from_secs(3));
+        drop(stream);
+    });
+
+    let config = MAConfig::default();
+    let message = message("webhook");
+    let calls = Rc::new(RefCell::new(Vec::new()));
+    let sinks: Vec<Box<dyn DeliverySink>> = vec![
+        Box::new(WebhookSink {
+            url: format!("http://127.0.0.1:{}/hook", port),
+            secret: None,
+        }),
+        Box::new(FakeSink {
+            name: "after",
+            fail: false,
+            calls: calls.clone(),
+        }),
+    ];
+    let start = Instant::now();
+    let report = deliver(&sinks, DeliveryContext::new("482913", &message, &config));
+    assert!(start.elapsed() < Duration::from_secs(2));
+    assert!(report.delivered(), "{:?}", report);
+    assert_eq!(*calls.borrow(), vec!["after:482913"]);
+    server.join().unwrap();
+}
+
+// 固定的前台应用
+struct FakeApps(Option<FrontmostApp>);
+
+impl FrontmostAppProvider for FakeApps {
+    fn frontmost_app(&self) -> Option<FrontmostApp> {
+        self.0.clone()
+    }
+}
+
+#[test]
+fn test_paste_allowlist() {
+    let safari = FrontmostApp {
+        id: Some("com.apple.Safari".to_string()),
+        name: Some("Safari".to_string()),
+    };
+    let slack = FrontmostApp {
+        id: Some("Slack".to_string()),
+        name: Some("slack".to_string()),
+    };
+
+    // 未配置时不限制
+    let config = MAConfig::default();
+    assert_eq!(
+        paste_target(&config, &FakeApps(Some(slack.clone()))),
+        PasteTarget::Paste(Some(slack.clone()))
+    );
+    assert_eq!(
+        paste_target(&config, &FakeApps(None)),
+        PasteTarget::Paste(None)
+    );
+
+    let config = MAConfig {
+        paste_allowlist: vec!["safari".to_string()],
+        paste_fallback: PasteFallback::FloatWindow,
+        ..MAConfig::default()
+    };
+    assert_eq!(
+        paste_target(&config, &FakeApps(Some(safari.clone()))),
+        PasteTarget::Paste(Some(safari))
+    );
+    assert_eq!(
+        paste_target(&config, &FakeApps(Some(slack))),
+        PasteTarget::Fallback(PasteFallback::FloatWindow)
+    );
+    // 无法识别前台应用时不粘贴
+    assert_eq!(
+        paste_target(&config, &FakeApps(None)),
+        PasteTarget::Fallback(PasteFallback::FloatWindow)
+    );
+}
@@ -0,0 +1,110 @@
+#![cfg(target_os = "linux")]
+
+mod common;
+
+use common::private_bus;
+use std::{
+    collections::HashMap,
+    sync::{mpsc, Mutex},
+    time::Duration,
+};
+
+use zbus::{blocking::connection, interface, zvariant::Value};
+use MessAuto::desktop_notification::{is_copy_action, show_code_notification};
+
+// 模拟通知服务，记录收到的标题、正文和按钮
+struct MockNotifications {
+    shown: Mutex<mpsc::Sender<(String, String, Vec<String>)>>,
+}
+
+#[interface(name = "org.freedesktop.Notifications")]
+impl MockNotifications {
+    #[allow(clippy::too_many_arguments)]
+    fn notify(
+        &self,
+        _app_name: &str,
+        _replaces_id: u32,
+        _app_icon: &str,
+        summary: &str,
+        body: &str,
+        actions: Vec<String>,
+        _hints: HashMap<&str, Value<'_>>,
+        _expire_timeout: i32,
+    ) -> u32 {
+        let _ = self
+            .shown
+            .lock()
+            .unwrap()
+            .send((summary.to_string(), body.to_string(), actions));
+        7
+    }
+
+    fn get_capabilities(&self) -> Vec<&str> {
+        vec!["actions", "body"]
+    }
+}
+
+#[test]
+#[ignore = "requires dbus-daemon"]
+fn test_notification_copy_action() {
+    let bus = private_bus();
+    // notify-rust 总是连接当前会话总线
+    std::env::set_var("DBUS_SESSION_BUS_ADDRESS", &bus.address);
+    let (shown_tx, shown_rx) = mpsc::channel();
+    let service = connection::Builder::address(bus.address.as_str())
+        .unwrap()
+        .name("org.freedesktop.Notifications")
+        .unwrap()
+        .serve_at(
+            "/org/freedesktop/Notifications",
+            MockNotifications {
+                shown: Mutex::new(shown_tx),
+            },
+        )
+        .unwrap()
+        .build()
+        .unwrap();
+
+    let (copied_tx, copied_rx) = mpsc::channel();
+    let expires_at = MessAuto::catch_up::unix_now() + 300;
+    show_code_notification("482913", Some("+15550100"), Some(expires_at), move || {
+        copied_tx.send(()).unwrap()
+    })
+    .unwrap();
+
+    let (summary, body, actions) = shown_rx.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert!(summary.contains("482913"));
+    assert!(body.contains("+15550100"));
+    assert!(body.contains('5'));
+    // default 动作没有文字，不会和复制按钮重复显示
+    assert_eq!(actions.len(), 4);
+    assert_eq!(actions[..2], ["default", ""]);
+    assert_eq!(actions[2], "copy");
+    assert!(!actions[3].is_empty());
+
+    // 等待通知句柄订阅信号后再模拟点击
+    for _ in 0..50 {
+        service
+            .emit_signal(
+                None::<&str>,
+                "/org/freedesktop/Notifications",
+                "org.freedesktop.Notifications",
+                "ActionInvoked",
+                &(7u32, "copy"),
+            )
+            .unwrap();
+        if copied_rx.recv_timeout(Duration::from_millis(100)).is_ok() {
+            return;
+        }
+    }
+    panic!("copy action was not handled");
+}
+
+#[test]
+fn test_is_copy_action() {
+    assert!(is_copy_action("copy", "Copy"));
+    assert!(is_copy_action("default", "Copy"));
+    // macOS 上回调按钮文字
+    assert!(is_copy_action("复制", "复制"));
+    assert!(!is_copy_action("__closed", "Copy"));
+}
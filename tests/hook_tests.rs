@@ -0,0 +1,107 @@
+#![cfg(unix)]
+
+use std::{
+    fs,
+    time::{Duration, Instant},
+};
+
+use serde_json::json;
+use MessAuto::{
+    catch_up::unix_now,
+    delivery::{deliver, DeliveryContext, SinkConfig},
+    hook::{run_hook, HookConfig},
+    source::IncomingMessage,
+    MAConfig,
+};
+
+fn shell(script: &str, timeout: u64) -> HookConfig {
+    HookConfig {
+        command: "sh".to_string(),
+        args: vec!["-c".to_string(), script.to_string()],
+        timeout,
+    }
+}
+
+#[test]
+fn test_hook_receives_env_and_stdin() {
+    let out = std::env::temp_dir().join(format!("messauto-hook-{}", std::process::id()));
+    let script = format!(
+        "printf '%s|%s|%s|%s|' \"$MESSAUTO_CODE\" \"$MESSAUTO_SENDER\" \"$MESSAUTO_CATEGORY\" \"$MESSAUTO_EXPIRES_AT\" > {0}; cat >> {0}",
+        out.display()
+    );
+    let message = IncomingMessage {
+        source: "imessage".to_string(),
+        sender: Some("+15550100".to_string()),
+        body: "Your code is 482913, valid for 5 minutes".to_string(),
+        received_at: 1714550400,
+        ..IncomingMessage::default()
+    };
+    let config = MAConfig::default();
+    let ctx = DeliveryContext::new("482913", &message, &config);
+    let outcome = run_hook(&shell(&script, 5), &ctx.payload()).unwrap();
+    assert!(outcome.success());
+
+    let written = fs::read_to_string(&out).unwrap();
+    let (env, stdin) = written.rsplit_once('|').unwrap();
+    assert_eq!(env, "482913|+15550100|sms|1714550700");
+    let stdin: serde_json::Value = serde_json::from_str(stdin).unwrap();
+    assert_eq!(stdin["code"], "482913");
+    assert_eq!(stdin["expires_at"], 1714550700);
+    let _ = fs::remove_file(&out);
+}
+
+#[test]
+fn test_hook_reports_failure_and_timeout() {
+    let payload = json!({"code": "482913"});
+    let outcome = run_hook(&shell("echo 'vault locked' >&2; exit 3", 5), &payload).unwrap();
+    assert!(!outcome.success());
+    assert_eq!(outcome.status.unwrap().code(), Some(3));
+    assert_eq!(outcome.stderr.trim(), "vault locked");
+
+    let started = Instant::now();
+    let outcome = run_hook(&shell("sleep 10", 1), &payload).unwrap();
+    assert!(outcome.status.is_none());
+    assert!(started.elapsed() < Duration::from_secs(5));
+}
+
+#[test]
+fn test_command_sink_does_not_block_delivery() {
+    let sink = SinkConfig::Command(shell("sleep 10", 15)).build();
+    let message = IncomingMessage {
+        source: "mail".to_string(),
+        body: "code 482913".to_string(),
+        received_at: unix_now(),
+        ..IncomingMessage::default()
+    };
+    let config = MAConfig::default();
+    let mut ctx = DeliveryContext::new("482913", &message, &config);
+    let started = Instant::now();
+    sink.deliver(&mut ctx).unwrap();
+    assert!(started.elapsed() < Duration::from_secs(1));
+
+    // 命令无法启动时投递失败，不会被当作已投递而触发邮箱整理
+    let missing = SinkConfig::Command(HookConfig {
+        command: "messauto-no-such-command".to_string(),
+        args: Vec::new(),
+        timeout: 5,
+    })
+    .build();
+    let report = deliver(
+        &[missing],
+        DeliveryContext::new("482913", &message, &config),
+    );
+    assert!(!report.delivered());
+
+    let sinks: Vec<SinkConfig> = serde_json::from_str(
+        r#"[{"type": "command", "command": "logger", "args": ["-t", "otp"]}]"#,
+    )
+    .unwrap();
+    assert_eq!(
+        sinks,
+        vec![SinkConfig::Command(HookConfig {
+            command: "logger".to_string(),
+            args: vec!["-t".to_string(), "otp".to_string()],
+            timeout: 10,
+        })]
+    );
+}
@@ -0,0 +1,187 @@
+use std::{
+    fs,
+    io::{BufRead, BufReader, Write},
+    net::TcpListener,
+    thread,
+};
+
+use MessAuto::{
+    housekeeping::{
+        drop_pending_housekeeping, maildir_apply, plan_housekeeping, queue_housekeeping,
+        HousekeepingAction, HousekeepingRule, HousekeepingTask, MailOrigin,
+    },
+    imap::{ImapAccount, ImapSecurity, ImapSession},
+};
+
+fn rule(sender: &str, source: Option<&str>, action: HousekeepingAction) -> HousekeepingRule {
+    HousekeepingRule {
+        sender: sender.to_string(),
+        source: source.map(str::to_string),
+        action,
+        folder: None,
+        after_minutes: 5,
+    }
+}
+
+#[test]
+fn test_plan_housekeeping() {
+    let rules = vec![
+        rule("*@bank.com", None, HousekeepingAction::MarkRead),
+        rule("*", Some("Work"), HousekeepingAction::Delete),
+    ];
+    let work = MailOrigin::Imap {
+        account: "Work".to_string(),
+        folder: "INBOX".to_string(),
+        uid: 7,
+    };
+    let home = MailOrigin::Imap {
+        account: "Home".to_string(),
+        folder: "INBOX".to_string(),
+        uid: 7,
+    };
+
+    let task = plan_housekeeping(&rules, &work, Some("otp@bank.com"), 1000).unwrap();
+    assert_eq!(task.action, HousekeepingAction::MarkRead);
+    assert_eq!(task.due, 1300);
+    let task = plan_housekeeping(&rules, &work, Some("noreply@shop.com"), 1000).unwrap();
+    assert_eq!(task.action, HousekeepingAction::Delete);
+    assert!(plan_housekeeping(&rules, &home, Some("noreply@shop.com"), 1000).is_none());
+}
+
+#[test]
+fn test_drop_pending_housekeeping() {
+    // 到期前退出时任务被放弃并返回，用于记录日志
+    let task = HousekeepingTask {
+        origin: MailOrigin::Imap {
+            account: "Work".to_string(),
+            folder: "INBOX".to_string(),
+            uid: 9,
+        },
+        action: HousekeepingAction::Delete,
+        folder: None,
+        due: i64::MAX,
+    };
+    queue_housekeeping(task.clone());
+    assert_eq!(drop_pending_housekeeping(), vec![task]);
+    assert!(drop_pending_housekeeping().is_empty());
+}
+
+fn maildir(root: &std::path::Path) {
+    for sub in ["cur", "new", "tmp"] {
+        fs::create_dir_all(root.join(sub)).unwrap();
+    }
+}
+
+#[test]
+fn test_maildir_housekeeping() {
+    let dir = std::env::temp_dir().join(format!("messauto-housekeeping-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    let inbox = dir.join("inbox");
+    let archive = dir.join("archive");
+    maildir(&inbox);
+    maildir(&archive);
+
+    // 标记已读：从 new/ 移到 cur/ 并加上 S 标记
+    let delivered = inbox.join("new/1.host");
+    fs::write(&delivered, "x").unwrap();
+    let read = maildir_apply(&delivered, HousekeepingAction::MarkRead, None)
+        .unwrap()
+        .unwrap();
+    assert_eq!(read, inbox.join("cur/1.host:2,S"));
+
+    // 邮件客户端已经移到 cur/ 后仍能找到，并保留原有标记
+    let delivered = inbox.join("new/2.host");
+    fs::write(inbox.join("cur/2.host:2,F"), "x").unwrap();
+    let read = maildir_apply(&delivered, HousekeepingAction::MarkRead, None)
+        .unwrap()
+        .unwrap();
+    assert_eq!(read, inbox.join("cur/2.host:2,FS"));
+
+    let moved = maildir_apply(
+        &delivered,
+        HousekeepingAction::Move,
+        Some(archive.to_str().unwrap()),
+    )
+    .unwrap()
+    .unwrap();
+    assert_eq!(moved, archive.join("cur/2.host:2,FS"));
+    assert!(moved.exists());
+
+    let delivered = inbox.join("new/3.host");
+    fs::write(&delivered, "x").unwrap();
+    assert_eq!(
+        maildir_apply(&delivered, HousekeepingAction::Delete, None).unwrap(),
+        None
+    );
+    assert!(!delivered.exists());
+}
+
+// 只回复 OK 的 IMAP 服务器，返回端口和收到的命令
+fn fake_imap_server(capability: &'static str) -> (u16, thread::JoinHandle<Vec<String>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let server = thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut out = stream;
+        let mut commands = Vec::new();
+        out.write_all(b"* OK ready\r\n").unwrap();
+        let mut line = String::new();
+        while reader.read_line(&mut line).unwrap_or(0) > 0 {
+            let (tag, command) = line.trim_end().split_once(' ').unwrap();
+            let reply = match command {
+                "CAPABILITY" => format!("* CAPABILITY {}\r\n", capability),
+                c if c.starts_with("SELECT") => "* OK [UIDNEXT 8] next\r\n".to_string(),
+                _ => String::new(),
+            };
+            out.write_all(format!("{}{} OK done\r\n", reply, tag).as_bytes())
+                .unwrap();
+            commands.push(command.to_string());
+            if command == "LOGOUT" {
+                break;
+            }
+            line.clear();
+        }
+        commands
+    });
+    (port, server)
+}
+
+#[test]
+fn test_imap_housekeeping_without_move_capability() {
+    for (capability, expunge) in [
+        ("IMAP4rev1 UIDPLUS", Some("UID EXPUNGE 7")),
+        // 不支持 UIDPLUS 时不能发送会清除其他已删除邮件的 EXPUNGE
+        ("IMAP4rev1", None),
+    ] {
+        let (port, server) = fake_imap_server(capability);
+        let account = ImapAccount {
+            name: "Test".to_string(),
+            host: "127.0.0.1".to_string(),
+            port,
+            security: ImapSecurity::Plain,
+            username: "user".to_string(),
+            password: None,
+            folders: vec!["INBOX".to_string()],
+            poll_interval: 60,
+        };
+        let mut session = ImapSession::connect(&account, "secret", "INBOX").unwrap();
+        session
+            .apply(7, HousekeepingAction::Move, Some("Archive"))
+            .unwrap();
+        session.logout().unwrap();
+
+        let commands = server.join().unwrap();
+        let expected: Vec<&str> = [
+            Some("UID COPY 7 \"Archive\""),
+            Some("UID STORE 7 +FLAGS.SILENT (\\Deleted)"),
+            expunge,
+            Some("LOGOUT"),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+        assert_eq!(commands[3..], expected);
+        assert!(!commands.iter().any(|c| c == "EXPUNGE"));
+    }
+}
@@ -0,0 +1,205 @@
+use std::{
+    io::{BufRead, BufReader, Read, Write},
+    net::{TcpListener, TcpStream},
+    thread,
+    time::Duration,
+};
+
+use MessAuto::imap::{parse_values, text_part, ImapAccount, ImapSecurity, ImapSession, ImapValue};
+
+const MULTIPART_STRUCTURE: &str = "(((\"TEXT\" \"PLAIN\" (\"CHARSET\" \"utf-8\") NIL NIL \"7BIT\" 30 1)(\"TEXT\" \"HTML\" (\"CHARSET\" \"utf-8\") NIL NIL \"7BIT\" 60 1) \"ALTERNATIVE\")(\"APPLICATION\" \"PDF\" NIL NIL NIL \"BASE64\" 90000) \"MIXED\")";
+
+// 本地 IMAP 测试服务器：按脚本应答，邮箱中原有 UID 1，连接后到达 UID 2
+fn stand_in_server(idle: bool) -> (u16, thread::JoinHandle<Vec<String>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let handle = thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        serve(stream, idle)
+    });
+    (port, handle)
+}
+
+fn serve(stream: TcpStream, idle: bool) -> Vec<String> {
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    let mut out = stream;
+    let mut commands = Vec::new();
+    let mut arrived = false;
+    out.write_all(b"* OK stand-in ready\r\n").unwrap();
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).unwrap_or(0) == 0 {
+            break;
+        }
+        let line = line.trim_end().to_string();
+        let (tag, command) = line.split_once(' ').unwrap();
+        commands.push(command.to_string());
+        let reply = match command {
+            c if c.starts_with("LOGIN") => String::new(),
+            "CAPABILITY" if idle => "* CAPABILITY IMAP4rev1 IDLE\r\n".to_string(),
+            "CAPABILITY" => "* CAPABILITY IMAP4rev1\r\n".to_string(),
+            c if c.starts_with("SELECT") => {
+                "* 1 EXISTS\r\n* OK [UIDNEXT 2] Predicted next UID\r\n".to_string()
+            }
+            "IDLE" => {
+                out.write_all(b"+ idling\r\n").unwrap();
+                thread::sleep(Duration::from_millis(300));
+                out.write_all(b"* 2 EXISTS\r\n").unwrap();
+                arrived = true;
+                let mut done = String::new();
+                reader.read_line(&mut done).unwrap();
+                assert_eq!(done.trim_end(), "DONE");
+                String::new()
+            }
+            "NOOP" => {
+                arrived = true;
+                "* 2 EXISTS\r\n".to_string()
+            }
+            "UID SEARCH UID 2:*" if arrived => "* SEARCH 2\r\n".to_string(),
+            "UID SEARCH UID 2:*" => "* SEARCH 1\r\n".to_string(),
+            "UID FETCH 2 (BODYSTRUCTURE)" => {
+                format!(
+                    "* 2 FETCH (UID 2 BODYSTRUCTURE {})\r\n",
+                    MULTIPART_STRUCTURE
+                )
+            }
+            "UID FETCH 2 (BODY.PEEK[HEADER] BODY.PEEK[1.1.MIME] BODY.PEEK[1.1])" => {
+                let header = "From: Service <noreply@example.com>\r\nSubject: Sign in\r\nMessage-ID: <imap-1@example.com>\r\nContent-Type: multipart/mixed; boundary=\"x\"\r\n\r\n";
+                let mime = "Content-Type: text/plain; charset=utf-8\r\n\r\n";
+                let body = "Your verification code is 482913\r\n";
+                format!(
+                    "* 2 FETCH (UID 2 BODY[HEADER] {{{}}}\r\n{} BODY[1.1.MIME] {{{}}}\r\n{} BODY[1.1] {{{}}}\r\n{})\r\n",
+                    header.len(),
+                    header,
+                    mime.len(),
+                    mime,
+                    body.len(),
+                    body
+                )
+            }
+            "LOGOUT" => {
+                out.write_all(format!("{} OK bye\r\n", tag).as_bytes())
+                    .unwrap();
+                break;
+            }
+            _ => {
+                out.write_all(format!("{} BAD unexpected\r\n", tag).as_bytes())
+                    .unwrap();
+                continue;
+            }
+        };
+        out.write_all(reply.as_bytes()).unwrap();
+        out.write_all(format!("{} OK done\r\n", tag).as_bytes())
+            .unwrap();
+    }
+    commands
+}
+
+fn account(port: u16) -> ImapAccount {
+    ImapAccount {
+        name: "Test".to_string(),
+        host: "127.0.0.1".to_string(),
+        port,
+        security: ImapSecurity::Plain,
+        username: "user".to_string(),
+        password: None,
+        folders: vec!["INBOX".to_string()],
+        poll_interval: 1,
+    }
+}
+
+#[test]
+fn test_parse_values_and_text_part() {
+    let values =
+        parse_values(b"* 2 FETCH (UID 2 BODY[HEADER] {5}\r\nab\r\nc FLAGS (\\Seen) X NIL)\r\n");
+    assert_eq!(values[0], ImapValue::Atom("*".to_string()));
+    let ImapValue::List(items) = &values[3] else {
+        panic!("expected list");
+    };
+    assert_eq!(items[3], ImapValue::Bytes(b"ab\r\nc".to_vec()));
+    assert_eq!(items[7], ImapValue::Nil);
+
+    let structure = parse_values(MULTIPART_STRUCTURE.as_bytes()).remove(0);
+    assert_eq!(text_part(&structure).as_deref(), Some("1.1"));
+    let html_only =
+        parse_values(b"((\"TEXT\" \"HTML\" NIL NIL NIL \"7BIT\" 10 1) \"MIXED\")").remove(0);
+    assert_eq!(text_part(&html_only).as_deref(), Some("1"));
+    let single = parse_values(b"(\"TEXT\" \"PLAIN\" NIL NIL NIL \"7BIT\" 10 1)").remove(0);
+    assert_eq!(text_part(&single), None);
+}
+
+#[test]
+fn test_imap_idle_fetches_text_part() {
+    let (port, server) = stand_in_server(true);
+    let mut session = ImapSession::connect(&account(port), "secret", "INBOX").unwrap();
+    assert!(session.idle_supported());
+    let messages = session.wait_for_messages(&|| false).unwrap();
+    assert_eq!(messages.len(), 1);
+    assert!(messages[0].body.contains("482913"));
+    assert_eq!(messages[0].id.as_deref(), Some("imap-1@example.com"));
+    drop(session);
+
+    let commands = server.join().unwrap();
+    assert_eq!(commands[0], "LOGIN \"user\" \"secret\"");
+    // 只拉取邮件头和正文文本部分，不下载附件
+    assert!(!commands.iter().any(|c| c.contains("BODY.PEEK[]")));
+}
+
+// 非 ASCII 密码不能放进引号字符串，以字面量发送
+#[test]
+fn test_imap_login_sends_literal_for_non_ascii_password() {
+    let password = "pässwörd\u{5bc6}";
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let server = thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut out = stream;
+        out.write_all(b"* OK stand-in ready\r\n").unwrap();
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        let login = line.trim_end().to_string();
+        let len: usize = login
+            .rsplit_once('{')
+            .and_then(|(_, len)| len.strip_suffix('}'))
+            .unwrap()
+            .parse()
+            .unwrap();
+        out.write_all(b"+ ready\r\n").unwrap();
+        let mut literal = vec![0; len];
+        reader.read_exact(&mut literal).unwrap();
+        let mut rest = String::new();
+        reader.read_line(&mut rest).unwrap();
+        out.write_all(b"M1 OK logged in\r\n").unwrap();
+        for reply in [
+            "* CAPABILITY IMAP4rev1\r\nM2 OK done\r\n",
+            "* OK [UIDNEXT 2] Predicted next UID\r\nM3 OK done\r\n",
+        ] {
+            let mut command = String::new();
+            reader.read_line(&mut command).unwrap();
+            out.write_all(reply.as_bytes()).unwrap();
+        }
+        (login, String::from_utf8(literal).unwrap(), rest)
+    });
+
+    ImapSession::connect(&account(port), password, "INBOX").unwrap();
+    let (login, literal, rest) = server.join().unwrap();
+    assert_eq!(login, format!("M1 LOGIN \"user\" {{{}}}", password.len()));
+    assert_eq!(literal, password);
+    assert_eq!(rest, "\r\n");
+}
+
+#[test]
+fn test_imap_polling_fallback() {
+    let (port, server) = stand_in_server(false);
+    let mut session = ImapSession::connect(&account(port), "secret", "INBOX").unwrap();
+    assert!(!session.idle_supported());
+    let messages = session.wait_for_messages(&|| false).unwrap();
+    assert_eq!(messages.len(), 1);
+    assert!(messages[0].body.contains("482913"));
+    drop(session);
+
+    let commands = server.join().unwrap();
+    assert!(commands.contains(&"NOOP".to_string()));
+    assert!(!commands.contains(&"IDLE".to_string()));
+}
@@ -0,0 +1,251 @@
+use std::{
+    io::{BufRead, BufReader, Read, Write},
+    net::{TcpListener, TcpStream},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+use serde_json::{json, Value};
+use MessAuto::jmap::{email_to_message, JmapAccount, JmapSession};
+
+// 本地 JMAP 测试服务器：新邮件 e1 在收件箱，e2 在垃圾邮件中，之后 e2 可被移入收件箱
+struct MockJmap {
+    port: u16,
+    delivered: Arc<AtomicBool>,
+    moved: Arc<AtomicBool>,
+    auth: Arc<Mutex<Vec<String>>>,
+}
+
+fn mock_jmap(push: bool) -> MockJmap {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let delivered = Arc::new(AtomicBool::new(false));
+    let moved = Arc::new(AtomicBool::new(false));
+    let auth = Arc::new(Mutex::new(Vec::new()));
+    let (d, m, a) = (delivered.clone(), moved.clone(), auth.clone());
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let (d, m, a) = (d.clone(), m.clone(), a.clone());
+            thread::spawn(move || serve(stream, port, push, &d, &m, &a));
+        }
+    });
+    MockJmap {
+        port,
+        delivered,
+        moved,
+        auth,
+    }
+}
+
+fn serve(
+    stream: TcpStream,
+    port: u16,
+    push: bool,
+    delivered: &AtomicBool,
+    moved: &AtomicBool,
+    auth: &Mutex<Vec<String>>,
+) {
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    let mut out = stream;
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line).unwrap();
+    let mut content_length = 0;
+    loop {
+        let mut header = String::new();
+        reader.read_line(&mut header).unwrap();
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        let (name, value) = header.split_once(": ").unwrap();
+        if name.eq_ignore_ascii_case("authorization") {
+            auth.lock().unwrap().push(value.to_string());
+        } else if name.eq_ignore_ascii_case("content-length") {
+            content_length = value.parse().unwrap();
+        }
+    }
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body).unwrap();
+
+    let path = request_line.split_whitespace().nth(1).unwrap();
+    let response = match path {
+        "/session" => {
+            let mut session = json!({
+                "apiUrl": format!("http://127.0.0.1:{}/api", port),
+                "primaryAccounts": {"urn:ietf:params:jmap:mail": "acc1"}
+            });
+            if push {
+                session["eventSourceUrl"] = json!(format!(
+                    "http://127.0.0.1:{}/events?types={{types}}&closeafter={{closeafter}}&ping={{ping}}",
+                    port
+                ));
+            }
+            session
+        }
+        "/api" => api(&serde_json::from_slice(&body).unwrap(), delivered, moved),
+        p if p.starts_with("/events?types=Email&closeafter=no") => {
+            out.write_all(b"HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\n\r\n")
+                .unwrap();
+            out.write_all(b": connected\n\n").unwrap();
+            thread::sleep(Duration::from_millis(300));
+            delivered.store(true, Ordering::SeqCst);
+            out.write_all(b"event: state\ndata: {\"changed\":{\"acc1\":{\"Email\":\"s2\"}}}\n\n")
+                .unwrap();
+            thread::sleep(Duration::from_secs(5));
+            return;
+        }
+        _ => json!({}),
+    };
+    let body = response.to_string();
+    write!(
+        out,
+        "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        body.len(),
+        body
+    )
+    .unwrap();
+}
+
+fn api(request: &Value, delivered: &AtomicBool, moved: &AtomicBool) -> Value {
+    let delivered = delivered.load(Ordering::SeqCst);
+    let moved = moved.load(Ordering::SeqCst);
+    let responses: Vec<Value> = request["methodCalls"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|call| {
+            let (method, args, id) = (&call[0], &call[1], &call[2]);
+            let result = match method.as_str().unwrap() {
+                "Mailbox/get" => json!({"list": [
+                    {"id": "mb-inbox", "name": "Inbox", "role": "inbox"},
+                    {"id": "mb-spam", "name": "Spam", "role": "junk"}
+                ]}),
+                "Email/get" if args["ids"] == json!([]) => json!({"state": "s1", "list": []}),
+                "Email/changes" if delivered && args["sinceState"] == "s1" => {
+                    json!({"newState": "s2", "hasMoreChanges": false, "created": ["e1", "e2"]})
+                }
+                "Email/changes" if moved && args["sinceState"] == "s2" => json!({
+                    "newState": "s3",
+                    "hasMoreChanges": false,
+                    "created": [],
+                    "updated": ["e2"]
+                }),
+                "Email/changes" => json!({
+                    "newState": args["sinceState"],
+                    "hasMoreChanges": false,
+                    "created": []
+                }),
+                "Email/get" if delivered && !moved && args["#ids"]["path"] == "/created" => {
+                    json!({"list": [
+                        {
+                            "id": "e1",
+                            "mailboxIds": {"mb-inbox": true},
+                            "from": [{"name": "Shop", "email": "noreply@shop.example"}],
+                            "messageId": ["jmap-1@shop.example"],
+                            "receivedAt": "2024-05-01T08:00:00Z",
+                            "textBody": [{"partId": "1"}],
+                            "htmlBody": [{"partId": "2"}],
+                            "bodyValues": {
+                                "1": {"value": "Your verification code is 731946"},
+                                "2": {"value": "<p>Your verification code is <b>731946</b></p>"}
+                            }
+                        },
+                        {
+                            "id": "e2",
+                            "mailboxIds": {"mb-spam": true},
+                            "textBody": [{"partId": "1"}],
+                            "bodyValues": {"1": {"value": "spam code 111111"}}
+                        }
+                    ]})
+                }
+                "Email/get" if moved && args["#ids"]["path"] == "/updated" => {
+                    json!({"list": [{"id": "e2", "mailboxIds": {"mb-inbox": true}}]})
+                }
+                "Email/get" if moved && args["ids"] == json!(["e2"]) => json!({"list": [{
+                    "id": "e2",
+                    "mailboxIds": {"mb-inbox": true},
+                    "textBody": [{"partId": "1"}],
+                    "bodyValues": {"1": {"value": "Your login code is 558201"}}
+                }]}),
+                _ => json!({"list": []}),
+            };
+            json!([method, result, id])
+        })
+        .collect();
+    json!({"methodResponses": responses})
+}
+
+fn account(port: u16) -> JmapAccount {
+    JmapAccount {
+        name: "Test".to_string(),
+        session_url: format!("http://127.0.0.1:{}/session", port),
+        username: None,
+        token: None,
+        mailboxes: vec!["inbox".to_string()],
+        poll_interval: 1,
+    }
+}
+
+#[test]
+fn test_email_to_message_falls_back_to_html() {
+    let email = json!({
+        "htmlBody": [{"partId": "2"}],
+        "textBody": [],
+        "bodyValues": {"2": {"value": "<p>Code: <b>482913</b></p>"}},
+        "receivedAt": "2024-05-01T08:00:00Z"
+    });
+    let message = email_to_message(&email);
+    assert!(message.body.contains("Code: 482913"));
+    assert_eq!(message.received_at, 1714550400);
+}
+
+#[test]
+fn test_jmap_event_source_push() {
+    let server = mock_jmap(true);
+    let mut session = JmapSession::connect(&account(server.port), "secret-token").unwrap();
+    assert!(session.push_supported());
+    let messages = session.wait_for_messages(&|| false).unwrap();
+    assert_eq!(messages.len(), 1);
+    assert_eq!(messages[0].body, "Your verification code is 731946");
+    assert_eq!(messages[0].sender.as_deref(), Some("noreply@shop.example"));
+    assert_eq!(messages[0].id.as_deref(), Some("jmap-1@shop.example"));
+    assert!(server
+        .auth
+        .lock()
+        .unwrap()
+        .iter()
+        .all(|auth| auth == "Bearer secret-token"));
+}
+
+#[test]
+fn test_jmap_polling_fallback() {
+    let server = mock_jmap(false);
+    let mut session = JmapSession::connect(&account(server.port), "secret-token").unwrap();
+    assert!(!session.push_supported());
+    assert!(session.fetch_new().unwrap().is_empty());
+    server.delivered.store(true, Ordering::SeqCst);
+    let messages = session.wait_for_messages(&|| false).unwrap();
+    assert_eq!(messages.len(), 1);
+    assert!(messages[0].body.contains("731946"));
+}
+
+#[test]
+fn test_jmap_picks_up_mail_moved_into_watched_mailbox() {
+    let server = mock_jmap(false);
+    let mut session = JmapSession::connect(&account(server.port), "secret-token").unwrap();
+    server.delivered.store(true, Ordering::SeqCst);
+    let messages = session.fetch_new().unwrap();
+    assert_eq!(messages.len(), 1);
+    assert!(messages[0].body.contains("731946"));
+    server.moved.store(true, Ordering::SeqCst);
+    let messages = session.fetch_new().unwrap();
+    assert_eq!(messages.len(), 1);
+    assert_eq!(messages[0].body, "Your login code is 558201");
+    assert_eq!(messages[0].source, "jmap:Test");
+    // 已经处理过的移动不再重复投递
+    assert!(session.fetch_new().unwrap().is_empty());
+}
@@ -0,0 +1,104 @@
+#![cfg(target_os = "linux")]
+
+mod common;
+
+use common::private_bus;
+use std::{thread, time::Duration};
+
+use zbus::{
+    blocking::{connection, Connection},
+    zvariant::{StructureBuilder, Value},
+};
+use MessAuto::{
+    kdeconnect::{parse_conversation_message, KdeConnectSource},
+    source::MessageSource,
+    supervisor::StopSignal,
+};
+
+const DEVICE_PATH: &str = "/modules/kdeconnect/devices/0123abcd";
+const INTERFACE: &str = "org.kde.kdeconnect.device.conversations";
+
+// 构造 KDE Connect 的 ConversationMessage (isa(s)xiixixa(xsss))
+fn conversation_message(body: &str, kind: i32, uid: i32) -> Value<'static> {
+    let addresses = vec![("+15550100".to_string(),)];
+    let attachments: Vec<(i64, String, String, String)> = Vec::new();
+    let message = StructureBuilder::new()
+        .add_field(1i32)
+        .add_field(body.to_string())
+        .add_field(addresses)
+        .add_field(1714550400000i64)
+        .add_field(kind)
+        .add_field(0i32)
+        .add_field(42i64)
+        .add_field(uid)
+        .add_field(-1i64)
+        .add_field(attachments)
+        .build();
+    Value::Structure(message)
+}
+
+fn emit(mock: &Connection, member: &str, message: Value<'static>) {
+    mock.emit_signal(
+        None::<&str>,
+        DEVICE_PATH,
+        INTERFACE,
+        member,
+        &Value::Value(Box::new(message)),
+    )
+    .unwrap();
+}
+
+#[test]
+fn test_parse_conversation_message() {
+    let message = parse_conversation_message(&conversation_message(
+        "Your verification code is 731946",
+        1,
+        7,
+    ))
+    .unwrap();
+    assert_eq!(message.source, "kdeconnect");
+    assert!(!message.is_mail());
+    assert_eq!(message.sender.as_deref(), Some("+15550100"));
+    assert_eq!(message.received_at, 1714550400);
+    assert_eq!(message.id.as_deref(), Some("42-7"));
+    // 发出的短信不参与检测
+    assert!(parse_conversation_message(&conversation_message("sent 123456", 2, 8)).is_none());
+}
+
+#[test]
+#[ignore = "requires dbus-daemon"]
+fn test_kdeconnect_source_receives_signals() {
+    let bus = private_bus();
+    let mut source = KdeConnectSource::new(Some(&bus.address)).unwrap();
+    let mock = connection::Builder::address(bus.address.as_str())
+        .unwrap()
+        .name("org.kde.kdeconnect")
+        .unwrap()
+        .build()
+        .unwrap();
+
+    emit(
+        &mock,
+        "conversationUpdated",
+        conversation_message("sent 123456", 2, 1),
+    );
+    emit(
+        &mock,
+        "conversationCreated",
+        conversation_message("Your verification code is 731946", 1, 2),
+    );
+
+    let stop = StopSignal::new();
+    let messages = source.next_messages(&stop).unwrap();
+    assert_eq!(messages.len(), 1);
+    assert_eq!(messages[0].body, "Your verification code is 731946");
+    assert_eq!(messages[0].id.as_deref(), Some("42-2"));
+
+    // 收到停止请求后及时返回
+    let signal = stop.clone();
+    thread::spawn(move || {
+        thread::sleep(Duration::from_millis(200));
+        signal.stop();
+    });
+    assert!(source.next_messages(&stop).unwrap().is_empty());
+}
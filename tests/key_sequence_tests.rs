@@ -0,0 +1,156 @@
+use std::{cell::RefCell, collections::HashMap, error::Error, rc::Rc, time::Duration};
+
+use MessAuto::{
+    frontmost::FrontmostApp,
+    key_sequence::{key_sequence_for, parse_key_sequence, run_key_sequence, KeyAction},
+    keystroke::{Key, KeyboardBackend},
+    MAConfig,
+};
+
+// 记录输入的文本和按键
+struct FakeKeyboard {
+    events: Rc<RefCell<Vec<String>>>,
+}
+
+impl KeyboardBackend for FakeKeyboard {
+    fn name(&self) -> &'static str {
+        "fake"
+    }
+
+    fn type_text(&self, text: &str, _delay: Duration) -> Result<(), Box<dyn Error>> {
+        self.events.borrow_mut().push(format!("type:{}", text));
+        Ok(())
+    }
+
+    fn press_key(&self, key: Key) -> Result<(), Box<dyn Error>> {
+        self.events.borrow_mut().push(format!("key:{:?}", key));
+        Ok(())
+    }
+}
+
+fn run(sequence: &str, code: &str) -> Vec<String> {
+    let events = Rc::new(RefCell::new(Vec::new()));
+    let keyboard = FakeKeyboard {
+        events: events.clone(),
+    };
+    let actions = parse_key_sequence(sequence).unwrap();
+    run_key_sequence(&actions, code, Some(&keyboard), Duration::ZERO, &mut || {
+        events.borrow_mut().push("paste".to_string());
+        Ok(())
+    })
+    .unwrap();
+    let events = events.borrow().clone();
+    events
+}
+
+#[test]
+fn test_parse_key_sequence() {
+    assert_eq!(
+        parse_key_sequence("{paste}{delay 200} {Tab}{enter}").unwrap(),
+        vec![
+            KeyAction::Paste,
+            KeyAction::Delay(200),
+            KeyAction::Key(Key::Tab),
+            KeyAction::Key(Key::Enter)
+        ]
+    );
+    assert_eq!(
+        parse_key_sequence("{type-split tab}").unwrap(),
+        vec![KeyAction::TypeSplit { tab: true }]
+    );
+    assert_eq!(parse_key_sequence("").unwrap(), vec![]);
+
+    assert!(parse_key_sequence("{paste}enter").is_err());
+    assert!(parse_key_sequence("{paste").is_err());
+    assert!(parse_key_sequence("{delay soon}").is_err());
+    assert!(parse_key_sequence("{escape}").is_err());
+}
+
+#[test]
+fn test_run_key_sequence() {
+    assert_eq!(
+        run("{paste}{delay 10}{tab}{enter}", "482913"),
+        vec!["paste", "key:Tab", "key:Enter"]
+    );
+    assert_eq!(
+        run("{type-split}", "4829"),
+        vec!["type:4", "type:8", "type:2", "type:9"]
+    );
+    assert_eq!(
+        run("{type-split tab}{enter}", "482"),
+        vec![
+            "type:4",
+            "key:Tab",
+            "type:8",
+            "key:Tab",
+            "type:2",
+            "key:Enter"
+        ]
+    );
+
+    // 没有键盘后端时只能执行粘贴
+    let actions = parse_key_sequence("{paste}{enter}").unwrap();
+    let mut pasted = false;
+    let result = run_key_sequence(&actions, "482913", None, Duration::ZERO, &mut || {
+        pasted = true;
+        Ok(())
+    });
+    assert!(result.is_err());
+    assert!(pasted);
+}
+
+#[test]
+fn test_key_sequence_per_app() {
+    let mut key_sequences = HashMap::new();
+    key_sequences.insert("com.apple.Safari".to_string(), "{paste}".to_string());
+    key_sequences.insert("firefox".to_string(), "{type-split}".to_string());
+    let config = MAConfig {
+        key_sequence: Some("{paste}{enter}".to_string()),
+        key_sequences,
+        ..MAConfig::default()
+    };
+    let safari = FrontmostApp {
+        id: Some("com.apple.Safari".to_string()),
+        name: Some("Safari".to_string()),
+    };
+    let firefox = FrontmostApp {
+        id: Some("Firefox".to_string()),
+        name: Some("firefox-bin".to_string()),
+    };
+    let terminal = FrontmostApp {
+        id: Some("org.gnome.Terminal".to_string()),
+        name: None,
+    };
+    assert_eq!(key_sequence_for(&config, Some(&safari)), Some("{paste}"));
+    assert_eq!(
+        key_sequence_for(&config, Some(&firefox)),
+        Some("{type-split}")
+    );
+    assert_eq!(
+        key_sequence_for(&config, Some(&terminal)),
+        Some("{paste}{enter}")
+    );
+    assert_eq!(key_sequence_for(&config, None), Some("{paste}{enter}"));
+    assert_eq!(key_sequence_for(&MAConfig::default(), Some(&safari)), None);
+
+    // id 和名称分别匹配不同的项时总是使用 id 匹配的项
+    let mut key_sequences = HashMap::new();
+    key_sequences.insert("firefox-bin".to_string(), "{type}".to_string());
+    key_sequences.insert("firefox".to_string(), "{paste}".to_string());
+    key_sequences.insert("FIREFOX".to_string(), "{type-split}".to_string());
+    let config = MAConfig {
+        key_sequences,
+        ..MAConfig::default()
+    };
+    for _ in 0..10 {
+        assert_eq!(
+            key_sequence_for(&config, Some(&firefox)),
+            Some("{type-split}")
+        );
+    }
+    let firefox = FrontmostApp {
+        id: Some("firefox".to_string()),
+        name: Some("firefox-bin".to_string()),
+    };
+    assert_eq!(key_sequence_for(&config, Some(&firefox)), Some("{paste}"));
+}
@@ -0,0 +1,218 @@
+use std::{
+    cell::RefCell,
+    error::Error,
+    ffi::{OsStr, OsString},
+    path::PathBuf,
+    sync::Mutex,
+    time::Duration,
+};
+
+use MessAuto::{
+    delivery::{DeliveryContext, DeliverySink, SinkConfig},
+    frontmost::{FrontmostApp, FrontmostAppProvider},
+    keystroke::{type_code, Key, KeyboardBackend, KeyboardBackendKind, KeystrokeSink, ToolBackend},
+    source::IncomingMessage,
+    MAConfig,
+};
+
+// 记录输入的文本和按键
+#[derive(Default)]
+struct FakeKeyboard {
+    events: RefCell<Vec<String>>,
+}
+
+impl KeyboardBackend for FakeKeyboard {
+    fn name(&self) -> &'static str {
+        "fake"
+    }
+
+    fn type_text(&self, text: &str, _delay: Duration) -> Result<(), Box<dyn Error>> {
+        self.events.borrow_mut().push(format!("type:{}", text));
+        Ok(())
+    }
+
+    fn press_key(&self, key: Key) -> Result<(), Box<dyn Error>> {
+        self.events.borrow_mut().push(format!("key:{:?}", key));
+        Ok(())
+    }
+}
+
+fn args(command: &std::process::Command) -> Vec<&OsStr> {
+    command.get_args().collect()
+}
+
+#[test]
+fn test_type_code_presses_enter_after_code() {
+    let keyboard = FakeKeyboard::default();
+    type_code(&keyboard, "482913", Duration::ZERO, false).unwrap();
+    type_code(&keyboard, "-12345", Duration::ZERO, true).unwrap();
+    assert_eq!(
+        *keyboard.events.borrow(),
+        vec!["type:482913", "type:-12345", "key:Enter"]
+    );
+}
+
+#[test]
+fn test_tool_backend_commands() {
+    let delay = Duration::from_millis(40);
+    let xdotool = ToolBackend(KeyboardBackendKind::Xdotool);
+    let command = xdotool.type_command(delay);
+    assert_eq!(command.get_program(), "xdotool");
+    assert_eq!(
+        args(&command),
+        ["type", "--clearmodifiers", "--delay", "40", "--file", "-"]
+    );
+
+    let ydotool = ToolBackend(KeyboardBackendKind::Ydotool);
+    assert_eq!(
+        args(&ydotool.type_command(delay)),
+        ["type", "--key-delay", "40", "--file", "-"]
+    );
+    assert_eq!(
+        args(&ydotool.key_command(Key::Enter)),
+        ["key", "28:1", "28:0"]
+    );
+
+    let wtype = ToolBackend(KeyboardBackendKind::Wtype);
+    assert_eq!(wtype.program(), "wtype");
+    assert_eq!(args(&wtype.type_command(delay)), ["-d", "40", "-"]);
+    assert_eq!(args(&wtype.key_command(Key::Enter)), ["-k", "Return"]);
+    assert_eq!(args(&wtype.key_command(Key::Tab)), ["-k", "Tab"]);
+    assert_eq!(
+        args(&xdotool.key_command(Key::Tab)),
+        ["key", "--clearmodifiers", "Tab"]
+    );
+}
+
+// 修改 PATH 的测试依次运行
+#[cfg(unix)]
+static PATH_LOCK: Mutex<()> = Mutex::new(());
+
+// 在 PATH 最前面放一个假的 xdotool，记录参数和标准输入；返回记录所在的目录和原来的 PATH
+#[cfg(unix)]
+fn fake_xdotool(name: &str) -> (PathBuf, OsString) {
+    use std::{fs, os::unix::fs::PermissionsExt};
+
+    let dir = std::env::temp_dir().join(format!("messauto-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let tool = dir.join("xdotool");
+    fs::write(
+        &tool,
+        format!(
+            "#!/bin/sh\necho \"$@\" > {0}/args\ncat > {0}/stdin\n",
+            dir.display()
+        ),
+    )
+    .unwrap();
+    fs::set_permissions(&tool, fs::Permissions::from_mode(0o755)).unwrap();
+    let path = std::env::var_os("PATH").unwrap_or_default();
+    let mut paths = vec![dir.clone()];
+    paths.extend(std::env::split_paths(&path));
+    std::env::set_var("PATH", std::env::join_paths(paths).unwrap());
+    (dir, path)
+}
+
+// 验证码只通过标准输入传递
+#[cfg(unix)]
+#[test]
+fn test_tool_backend_types_from_stdin() {
+    use std::fs;
+
+    let _lock = PATH_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let (dir, path) = fake_xdotool("xdotool");
+    ToolBackend(KeyboardBackendKind::Xdotool)
+        .type_text("-482913", Duration::from_millis(40))
+        .unwrap();
+    assert_eq!(fs::read_to_string(dir.join("stdin")).unwrap(), "-482913");
+    assert!(!fs::read_to_string(dir.join("args"))
+        .unwrap()
+        .contains("482913"));
+
+    std::env::set_var("PATH", path);
+    let _ = fs::remove_dir_all(&dir);
+}
+
+// 固定的前台应用
+struct FakeApps(Option<FrontmostApp>);
+
+impl FrontmostAppProvider for FakeApps {
+    fn frontmost_app(&self) -> Option<FrontmostApp> {
+        self.0.clone()
+    }
+}
+
+// 模拟键盘输入与粘贴一样只在 paste_allowlist 中的应用里进行
+#[cfg(unix)]
+#[test]
+fn test_keystroke_sink_respects_paste_allowlist() {
+    use std::fs;
+
+    let _lock = PATH_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let (dir, path) = fake_xdotool("keystroke-allowlist");
+    let config = MAConfig {
+        paste_allowlist: vec!["safari".to_string()],
+        ..MAConfig::default()
+    };
+    let message = IncomingMessage {
+        source: "imessage".to_string(),
+        sender: None,
+        subject: None,
+        body: "Your code is 482913".to_string(),
+        received_at: 0,
+        id: None,
+        origin: None,
+    };
+    let sink = |app: &str| KeystrokeSink {
+        backend: KeyboardBackendKind::Xdotool,
+        key_delay: 0,
+        apps: Box::new(FakeApps(Some(FrontmostApp {
+            id: None,
+            name: Some(app.to_string()),
+        }))),
+    };
+
+    // 不在列表中时改为复制到剪贴板，测试环境中可能没有剪贴板，只检查没有输入
+    let _ = sink("Slack").deliver(&mut DeliveryContext::new("482913", &message, &config));
+    assert!(!dir.join("stdin").exists());
+
+    sink("Safari")
+        .deliver(&mut DeliveryContext::new("482913", &message, &config))
+        .unwrap();
+    assert_eq!(fs::read_to_string(dir.join("stdin")).unwrap(), "482913");
+
+    std::env::set_var("PATH", path);
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_auto_backend_candidates() {
+    use KeyboardBackendKind::*;
+    use MessAuto::keystroke::candidates;
+    assert_eq!(candidates(Auto, true, true), vec![Wtype, Ydotool]);
+    assert_eq!(candidates(Auto, false, true), vec![Xdotool, Ydotool]);
+    assert_eq!(candidates(Auto, false, false), vec![Ydotool]);
+    assert_eq!(candidates(Xdotool, true, false), vec![Xdotool]);
+}
+
+#[test]
+fn test_keystroke_sink_config() {
+    let sinks: Vec<SinkConfig> = serde_json::from_str(
+        r#"[{"type": "keystroke"}, {"type": "keystroke", "backend": "ydotool", "key_delay": 80}]"#,
+    )
+    .unwrap();
+    assert_eq!(
+        sinks,
+        vec![
+            SinkConfig::Keystroke {
+                backend: KeyboardBackendKind::Auto,
+                key_delay: 30
+            },
+            SinkConfig::Keystroke {
+                backend: KeyboardBackendKind::Ydotool,
+                key_delay: 80
+            }
+        ]
+    );
+}
@@ -1,8 +1,20 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+use std::time::{Duration, Instant};
+
 use home::home_dir;
 
+use MessAuto::catch_up::{catch_up_action, CatchUpAction, CatchUpPolicy};
+use MessAuto::dedup::Deduplicator;
+use MessAuto::emlx_tracker::{canonical_emlx_path, EmlxTracker};
+use MessAuto::mail_scope::{
+    discover_mailboxes, mailbox_selected, owning_mailbox, pattern_matches, watch_roots,
+};
 use MessAuto::{
-    check_captcha_or_other, check_for_updates, config_path, get_captchas, get_real_captcha,
-    get_sys_locale,
+    check_captcha_or_other, check_for_updates, config_path, get_captcha_lifetime, get_captchas,
+    get_real_captcha, get_sys_locale, query_latest_message, MAConfig,
 };
 
 #[test]
@@ -119,6 +131,254 @@ fn test_get_real_captcha() {
     assert_eq!(result, "047289");
 }
 
+#[test]
+fn test_query_latest_message_skips_outgoing_and_group_chats() {
+    let db_path = std::env::temp_dir().join(format!("messauto-chat-{}.db", std::process::id()));
+    let _ = std::fs::remove_file(&db_path);
+    // 模拟 chat.db 的最小表结构，时间戳使用 Apple 纪元（2001-01-01）的纳秒数
+    let schema = "
+        CREATE TABLE message (ROWID INTEGER PRIMARY KEY, guid TEXT, text TEXT, date INTEGER, is_from_me INTEGER, handle_id INTEGER);
+        CREATE TABLE handle (ROWID INTEGER PRIMARY KEY, id TEXT);
+        CREATE TABLE chat (ROWID INTEGER PRIMARY KEY, style INTEGER);
+        CREATE TABLE chat_message_join (chat_id INTEGER, message_id INTEGER);
+        INSERT INTO handle VALUES (1, '+8610690000');
+        INSERT INTO chat VALUES (1, 45), (2, 43);
+        INSERT INTO message VALUES
+            (1, 'guid-1', '您的验证码为 111111', (strftime('%s','now') - 978307200 - 3) * 1000000000, 0, 1),
+            (2, 'guid-2', '我的新号码是 222222', (strftime('%s','now') - 978307200 - 2) * 1000000000, 1, 1),
+            (3, 'guid-3', '群里的验证码 333333', (strftime('%s','now') - 978307200 - 1) * 1000000000, 0, 1);
+        INSERT INTO chat_message_join VALUES (1, 1), (1, 2), (2, 3);
+    ";
+    let status = Command::new("sqlite3")
+        .arg(&db_path)
+        .arg(schema)
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let mut config = MAConfig::default();
+    let message = query_latest_message(&db_path, &config).unwrap();
+    assert_eq!(message.text, "您的验证码为 111111");
+    assert_eq!(message.guid.as_deref(), Some("guid-1"));
+    assert_eq!(message.sender.as_deref(), Some("+8610690000"));
+
+    config.listen_own_messages = true;
+    assert_eq!(
+        query_latest_message(&db_path, &config).unwrap().text,
+        "我的新号码是 222222"
+    );
+
+    config.listen_group_chats = true;
+    assert_eq!(
+        query_latest_message(&db_path, &config).unwrap().text,
+        "群里的验证码 333333"
+    );
+
+    let _ = std::fs::remove_file(&db_path);
+}
+
+#[test]
+fn test_catch_up_action() {
+    let activated_at = 1_000;
+    let now = 1_010;
+
+    // 启动/唤醒之后到达的信息总是正常处理
+    for policy in [
+        CatchUpPolicy::Ignore,
+        CatchUpPolicy::Show,
+        CatchUpPolicy::Process,
+    ] {
+        assert_eq!(
+            catch_up_action(policy, 30, 1_005, activated_at, now),
+            CatchUpAction::Deliver
+        );
+    }
+
+    assert_eq!(
+        catch_up_action(CatchUpPolicy::Ignore, 30, 900, activated_at, now),
+        CatchUpAction::Skip
+    );
+    // 启动前不久到达的信息默认只展示，只有 Process 策略才会自动投递
+    assert_eq!(
+        catch_up_action(CatchUpPolicy::Ignore, 30, 990, activated_at, now),
+        CatchUpAction::ShowOnly
+    );
+    assert_eq!(
+        catch_up_action(CatchUpPolicy::Show, 30, 900, activated_at, now),
+        CatchUpAction::ShowOnly
+    );
+    assert_eq!(
+        catch_up_action(CatchUpPolicy::Process, 30, 900, activated_at, now),
+        CatchUpAction::Deliver
+    );
+}
+
+#[test]
+fn test_deduplicator() {
+    let mut dedup = Deduplicator::new();
+    assert!(!dedup.check_and_record("123456", Some("+8610690000"), Some("guid-1"), 60, false, 0));
+    // 同一条信息再次被处理
+    assert!(dedup.check_and_record("123456", Some("+8610690000"), Some("guid-1"), 60, false, 5));
+    // 同一验证码经邮件再次到达
+    assert!(dedup.check_and_record(
+        "123456",
+        Some("noreply@example.com"),
+        Some("<a@b>"),
+        60,
+        false,
+        10
+    ));
+    // 同一封邮件即使验证码不同（例如提取结果变化）也视为重复
+    assert!(dedup.check_and_record("654321", None, Some("<a@b>"), 60, false, 15));
+    // 要求发件人相同时，不同发件人的相同验证码不算重复
+    assert!(!dedup.check_and_record("123456", Some("other@example.com"), None, 60, true, 20));
+    // 超出窗口期后不再视为重复
+    assert!(!dedup.check_and_record(
+        "123456",
+        Some("+8610690000"),
+        Some("guid-1"),
+        60,
+        false,
+        200
+    ));
+}
+
+#[test]
+fn test_pattern_matches() {
+    assert!(pattern_matches("INBOX", "INBOX"));
+    assert!(pattern_matches("inbox", "INBOX"));
+    assert!(pattern_matches("*", "Work/Verification"));
+    assert!(pattern_matches("Work/*", "Work/Verification"));
+    assert!(pattern_matches("*Verif*", "Work/Verification"));
+    assert!(pattern_matches("Junk?", "Junk1"));
+    assert!(!pattern_matches("Work/*", "INBOX"));
+    assert!(!pattern_matches("INBOX", "INBOX/Sub"));
+}
+
+#[test]
+fn test_discover_and_select_mailboxes() {
+    let data_dir = std::env::temp_dir().join(format!("messauto-mail-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&data_dir);
+    for dir in [
+        "MailData",
+        "ACCOUNT-A/INBOX.mbox/UUID/Data/Messages",
+        "ACCOUNT-A/Junk.mbox",
+        "ACCOUNT-A/Work.mbox/Verification.mbox",
+        "ACCOUNT-B/INBOX.mbox",
+    ] {
+        fs::create_dir_all(data_dir.join(dir)).unwrap();
+    }
+    let names = HashMap::from([("ACCOUNT-A".to_string(), "Gmail".to_string())]);
+
+    let mailboxes = discover_mailboxes(&data_dir, &names);
+    let found: Vec<(&str, &str)> = mailboxes
+        .iter()
+        .map(|m| (m.account_name.as_str(), m.name.as_str()))
+        .collect();
+    assert_eq!(
+        found,
+        vec![
+            ("Gmail", "INBOX"),
+            ("Gmail", "Junk"),
+            ("Gmail", "Work"),
+            ("Gmail", "Work/Verification"),
+            ("ACCOUNT-B", "INBOX"),
+        ]
+    );
+
+    let include = vec!["INBOX".to_string(), "Work*".to_string()];
+    let exclude = vec!["Work".to_string()];
+    let accounts = HashMap::from([("ACCOUNT-B".to_string(), false)]);
+    let selected: Vec<_> = mailboxes
+        .iter()
+        .filter(|m| mailbox_selected(m, &include, &exclude, &accounts))
+        .cloned()
+        .collect();
+    let selected_names: Vec<&str> = selected.iter().map(|m| m.name.as_str()).collect();
+    assert_eq!(selected_names, vec!["INBOX", "Work/Verification"]);
+    assert_eq!(watch_roots(&selected).len(), 2);
+    assert_eq!(watch_roots(&mailboxes).len(), 4);
+
+    let emlx = data_dir.join("ACCOUNT-A/INBOX.mbox/UUID/Data/Messages/1.emlx");
+    assert_eq!(
+        owning_mailbox(&emlx),
+        Some(data_dir.join("ACCOUNT-A/INBOX.mbox"))
+    );
+
+    let _ = fs::remove_dir_all(&data_dir);
+}
+
+#[test]
+fn test_canonical_emlx_path() {
+    let dir = Path::new("/Mail/V10/ACCOUNT/INBOX.mbox/Messages");
+    for name in ["123.emlx", "123.emlx.tmp", "123.partial.emlx"] {
+        assert_eq!(
+            canonical_emlx_path(&dir.join(name)),
+            Some(dir.join("123.emlx"))
+        );
+    }
+    assert_eq!(canonical_emlx_path(&dir.join("Info.plist")), None);
+}
+
+#[test]
+fn test_emlx_tracker() {
+    let dir = std::env::temp_dir().join(format!("messauto-emlx-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let start = Instant::now();
+    let mut tracker = EmlxTracker::new();
+
+    // 只有 .partial.emlx 时一直等待完整邮件
+    let partial = dir.join("1.partial.emlx");
+    fs::write(&partial, "headers only").unwrap();
+    let full = tracker.observe(&partial, start).unwrap();
+    assert!(tracker.due(start).is_empty());
+    assert!(tracker.due(start + Duration::from_secs(5)).is_empty());
+    assert!(!tracker.is_idle());
+
+    // 完整邮件写入后，等待文件稳定再读取
+    fs::write(&full, "full message").unwrap();
+    let now = start + Duration::from_secs(6);
+    tracker.observe(&full, now);
+    assert!(tracker.due(now).is_empty());
+    assert_eq!(
+        tracker.due(now + Duration::from_secs(1)),
+        vec![full.clone()]
+    );
+
+    // 解析失败时有限次重试
+    let mut now = now + Duration::from_secs(1);
+    let mut retries = 0;
+    while tracker.mark_failed(&full, now) {
+        retries += 1;
+        now += Duration::from_secs(60);
+        assert_eq!(tracker.due(now), vec![full.clone()]);
+    }
+    assert!(retries < 10);
+    assert!(tracker.is_idle());
+
+    // 处理完成后，后续的修改事件不再触发读取
+    let other = dir.join("2.emlx");
+    fs::write(&other, "message").unwrap();
+    tracker.observe(&other, now);
+    tracker.mark_done(&other, now);
+    assert_eq!(tracker.observe(&other, now), None);
+    assert!(tracker.due(now + Duration::from_secs(1)).is_empty());
+    // 一段时间后 Mail.app 重写文件也不会再次投递
+    let later = now + Duration::from_secs(600);
+    assert_eq!(tracker.observe(&other, later), None);
+    assert!(tracker.due(later).is_empty());
+
+    // 已处理的记录不会无限增长，超过保留时间后被淘汰
+    let third = dir.join("3.emlx");
+    let much_later = now + Duration::from_secs(7200);
+    tracker.mark_done(&third, much_later);
+    assert_eq!(tracker.observe(&third, much_later), None);
+    assert_eq!(tracker.observe(&other, much_later), Some(other.clone()));
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
 // #[test]
 // fn test_check_for_updates() {
 //     // let need_update = check_for_updates();
@@ -128,3 +388,24 @@ fn test_get_real_captcha() {
 //     println!("error:{:?}", need_update);
 //     assert_eq!(need_update.is_ok(), true);
 // }
+
+#[test]
+fn test_get_captcha_lifetime() {
+    assert_eq!(
+        get_captcha_lifetime("Your code is 482913. It is valid for 10 minutes."),
+        Some(600)
+    );
+    assert_eq!(
+        get_captcha_lifetime("Code 731946 expires in 30 seconds"),
+        Some(30)
+    );
+    assert_eq!(
+        get_captcha_lifetime("【某某】验证码 236812，5分钟内有效"),
+        Some(300)
+    );
+    assert_eq!(
+        get_captcha_lifetime("验证码 236812，有效期为2小时"),
+        Some(7200)
+    );
+    assert_eq!(get_captcha_lifetime("Your code is 482913"), None);
+}
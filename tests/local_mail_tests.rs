@@ -0,0 +1,160 @@
+mod common;
+
+use common::temp_dir;
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use MessAuto::local_mail::{split_mbox, LocalMailWatcher, MboxReader};
+
+fn raw_mail(message_id: &str, body: &str) -> String {
+    format!(
+        "From: Example <noreply@example.com>\r\n\
+         To: me@example.com\r\n\
+         Subject: Your verification code\r\n\
+         Message-ID: <{}>\r\n\
+         Date: Mon, 19 Oct 2026 10:00:00 +0000\r\n\
+         \r\n\
+         {}\r\n",
+        message_id, body
+    )
+}
+
+#[test]
+fn test_split_mbox() {
+    let mbox = "From noreply@example.com Mon Oct 19 10:00:00 2026\n\
+                Subject: first\n\
+                \n\
+                >From the team\n\
+                \n\
+                From noreply@example.com Mon Oct 19 10:01:00 2026\n\
+                Subject: second\n\
+                \n\
+                body\n";
+    let messages = split_mbox(mbox.as_bytes());
+    assert_eq!(messages.len(), 2);
+    assert_eq!(
+        String::from_utf8_lossy(&messages[0]),
+        "Subject: first\n\nFrom the team\n\n"
+    );
+    assert_eq!(
+        String::from_utf8_lossy(&messages[1]),
+        "Subject: second\n\nbody\n"
+    );
+}
+
+fn append(path: &PathBuf, data: &str) {
+    let mut file = fs::OpenOptions::new().append(true).open(path).unwrap();
+    file.write_all(data.as_bytes()).unwrap();
+}
+
+#[test]
+fn test_mbox_reader_partial_writes() {
+    let dir = temp_dir("mbox-reader");
+    let mbox = dir.join("inbox");
+    fs::write(&mbox, "").unwrap();
+    let mut reader = MboxReader::at_end(&mbox);
+    let start = Instant::now();
+    let separator = "From noreply@example.com Mon Oct 19 10:00:00 2026\n";
+
+    // 一封邮件分两次写入
+    let mail = format!("{}{}\n", separator, raw_mail("a@example.com", "222222"));
+    let (head, tail) = mail.split_at(mail.len() / 2);
+    append(&mbox, head);
+    assert!(reader.read_appended(&mbox, start).is_empty());
+    append(&mbox, tail);
+    assert!(reader.read_appended(&mbox, start).is_empty());
+    assert!(reader.flush(start).is_none());
+    let flushed = reader.flush(start + Duration::from_secs(2)).unwrap();
+    assert!(String::from_utf8_lossy(&flushed).contains("<a@example.com>"));
+    assert!(String::from_utf8_lossy(&flushed).contains("222222"));
+    assert!(reader.flush(start + Duration::from_secs(3)).is_none());
+
+    // 静止时间内先后追加两封邮件
+    let now = start + Duration::from_secs(4);
+    append(
+        &mbox,
+        &format!("{}{}\n", separator, raw_mail("b@example.com", "333333")),
+    );
+    assert!(reader.read_appended(&mbox, now).is_empty());
+    append(
+        &mbox,
+        &format!("{}{}\n", separator, raw_mail("c@example.com", "444444")),
+    );
+    let messages = reader.read_appended(&mbox, now + Duration::from_millis(100));
+    assert_eq!(messages.len(), 1);
+    assert!(!String::from_utf8_lossy(&messages[0]).starts_with("From "));
+    assert!(String::from_utf8_lossy(&messages[0]).contains("<b@example.com>"));
+    let last = reader.flush(now + Duration::from_secs(2)).unwrap();
+    assert!(String::from_utf8_lossy(&last).contains("<c@example.com>"));
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn test_maildir_source() {
+    let maildir = temp_dir("maildir");
+    for sub in ["cur", "new", "tmp"] {
+        fs::create_dir_all(maildir.join(sub)).unwrap();
+    }
+    // 启动前已有的邮件不处理
+    fs::write(
+        maildir.join("new/old.host"),
+        raw_mail("old@example.com", "123456"),
+    )
+    .unwrap();
+
+    let mut watcher = LocalMailWatcher::new(&[maildir.to_string_lossy().to_string()], &[]).unwrap();
+
+    // 按 Maildir 规范先写 tmp/ 再重命名到 new/
+    let tmp = maildir.join("tmp/1.host");
+    fs::write(&tmp, raw_mail("new@example.com", "Your code is 654321")).unwrap();
+    fs::rename(&tmp, maildir.join("new/1.host")).unwrap();
+
+    let message = watcher.next_message(Duration::from_secs(5)).unwrap();
+    assert_eq!(message.source, "maildir");
+    assert_eq!(message.id.as_deref(), Some("new@example.com"));
+    assert_eq!(message.sender.as_deref(), Some("noreply@example.com"));
+    assert!(message.body.contains("654321"));
+    assert!(watcher.next_message(Duration::from_millis(500)).is_none());
+
+    let _ = fs::remove_dir_all(&maildir);
+}
+
+#[test]
+fn test_mbox_source() {
+    let dir = temp_dir("mbox");
+    let mbox = dir.join("inbox");
+    fs::write(
+        &mbox,
+        format!(
+            "From noreply@example.com Mon Oct 19 09:00:00 2026\n{}\n",
+            raw_mail("old@example.com", "111111")
+        ),
+    )
+    .unwrap();
+
+    let mut watcher = LocalMailWatcher::new(&[], &[mbox.to_string_lossy().to_string()]).unwrap();
+
+    let mut file = fs::OpenOptions::new().append(true).open(&mbox).unwrap();
+    for (id, code) in [("a@example.com", "222222"), ("b@example.com", "333333")] {
+        write!(
+            file,
+            "From noreply@example.com Mon Oct 19 10:00:00 2026\n{}\n",
+            raw_mail(id, &format!("Your code is {}", code))
+        )
+        .unwrap();
+    }
+    file.flush().unwrap();
+
+    let first = watcher.next_message(Duration::from_secs(5)).unwrap();
+    assert_eq!(first.id.as_deref(), Some("a@example.com"));
+    // 最后一封邮件在文件静止后才被视为完整
+    let second = watcher.next_message(Duration::from_secs(5)).unwrap();
+    assert_eq!(second.id.as_deref(), Some("b@example.com"));
+    assert!(second.body.contains("333333"));
+    assert!(watcher.next_message(Duration::from_millis(500)).is_none());
+
+    let _ = fs::remove_dir_all(&dir);
+}
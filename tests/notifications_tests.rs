@@ -0,0 +1,123 @@
+#![cfg(target_os = "linux")]
+
+mod common;
+
+use common::private_bus;
+use std::collections::HashMap;
+
+use zbus::{
+    blocking::{connection, Connection},
+    interface,
+    zvariant::Value,
+};
+use MessAuto::{
+    notifications::{app_allowed, strip_markup, NotificationSource},
+    source::MessageSource,
+    supervisor::StopSignal,
+};
+
+// 模拟通知服务
+struct MockNotifications;
+
+#[interface(name = "org.freedesktop.Notifications")]
+impl MockNotifications {
+    #[allow(clippy::too_many_arguments)]
+    fn notify(
+        &self,
+        _app_name: &str,
+        _replaces_id: u32,
+        _app_icon: &str,
+        _summary: &str,
+        _body: &str,
+        _actions: Vec<&str>,
+        _hints: HashMap<&str, Value<'_>>,
+        _expire_timeout: i32,
+    ) -> u32 {
+        1
+    }
+}
+
+fn notify(client: &Connection, app: &str, summary: &str, body: &str, entry: Option<&str>) {
+    let mut hints: HashMap<&str, Value> = HashMap::new();
+    if let Some(entry) = entry {
+        hints.insert("desktop-entry", Value::from(entry));
+    }
+    client
+        .call_method(
+            Some("org.freedesktop.Notifications"),
+            "/org/freedesktop/Notifications",
+            Some("org.freedesktop.Notifications"),
+            "Notify",
+            &(
+                app,
+                0u32,
+                "",
+                summary,
+                body,
+                Vec::<&str>::new(),
+                hints,
+                -1i32,
+            ),
+        )
+        .unwrap();
+}
+
+#[test]
+fn test_app_allowed() {
+    let allowlist = vec!["Signal".to_string(), "org.gnome.Authy".to_string()];
+    assert!(app_allowed(&allowlist, "signal", None));
+    assert!(app_allowed(&allowlist, "Authy", Some("org.gnome.Authy")));
+    assert!(!app_allowed(&allowlist, "Firefox", None));
+    assert!(app_allowed(&["*".to_string()], "Firefox", None));
+    assert!(!app_allowed(&[], "Signal", None));
+    // 忽略 MessAuto 自己发出的通知
+    assert!(!app_allowed(&["*".to_string()], "MessAuto", None));
+}
+
+#[test]
+fn test_strip_markup() {
+    assert_eq!(
+        strip_markup("<b>482913</b> is your code &amp; expires"),
+        "482913 is your code & expires"
+    );
+    assert_eq!(strip_markup("code: 1234"), "code: 1234");
+}
+
+#[test]
+#[ignore = "requires dbus-daemon"]
+fn test_notification_source_monitors_notify_calls() {
+    let bus = private_bus();
+    let _service = connection::Builder::address(bus.address.as_str())
+        .unwrap()
+        .name("org.freedesktop.Notifications")
+        .unwrap()
+        .serve_at("/org/freedesktop/Notifications", MockNotifications)
+        .unwrap()
+        .build()
+        .unwrap();
+    let allowlist = vec!["Signal".to_string(), "org.gnome.Authy".to_string()];
+    let mut source = NotificationSource::new(Some(&bus.address), allowlist).unwrap();
+    let client = connection::Builder::address(bus.address.as_str())
+        .unwrap()
+        .build()
+        .unwrap();
+
+    notify(&client, "Firefox", "Download complete", "code 111111", None);
+    notify(
+        &client,
+        "Authy",
+        "Your code",
+        "<b>482913</b> expires soon",
+        Some("org.gnome.Authy"),
+    );
+
+    let stop = StopSignal::new();
+    let messages = source.next_messages(&stop).unwrap();
+    assert_eq!(messages.len(), 1);
+    assert_eq!(messages[0].source, "notification");
+    assert!(!messages[0].is_mail());
+    assert_eq!(messages[0].sender.as_deref(), Some("Authy"));
+    assert_eq!(messages[0].subject.as_deref(), Some("Your code"));
+    assert!(messages[0].body.starts_with("Your code\n"));
+    assert!(messages[0].body.contains("482913 expires soon"));
+}
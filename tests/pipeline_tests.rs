@@ -0,0 +1,204 @@
+use std::collections::HashMap;
+use std::error::Error;
+
+use MessAuto::catch_up::{mark_activated, unix_now, CatchUpPolicy};
+use MessAuto::delivery::SinkConfig;
+use MessAuto::hook::HookConfig;
+use MessAuto::pipeline::{evaluate, plan, Decision};
+use MessAuto::source::{run_source, IncomingMessage, MessageSource};
+use MessAuto::supervisor::StopSignal;
+use MessAuto::MAConfig;
+
+fn message(source: &str, body: &str, id: &str) -> IncomingMessage {
+    IncomingMessage {
+        source: source.to_string(),
+        sender: Some("noreply@example.com".to_string()),
+        subject: None,
+        body: body.to_string(),
+        received_at: unix_now(),
+        id: Some(id.to_string()),
+        origin: None,
+    }
+}
+
+#[test]
+fn test_evaluate_is_the_same_for_every_source() {
+    let config = MAConfig::default();
+    for (i, source) in ["imessage", "mail", "imap:Work", "jmap:Fastmail"]
+        .iter()
+        .enumerate()
+    {
+        let code = format!("73{:04}", i);
+        let body = format!("Your verification code is {}", code);
+        let id = format!("pipeline-{}", i);
+        assert_eq!(
+            evaluate(&message(source, &body, &id), &config),
+            Decision::Deliver(code.clone())
+        );
+        // 同一封信息经过另一个信息源再次到达时不重复投递
+        assert_eq!(
+            evaluate(&message("maildir", &body, &id), &config),
+            Decision::Duplicate
+        );
+    }
+
+    assert_eq!(
+        evaluate(&message("mail", "Hello there", "pipeline-plain"), &config),
+        Decision::Ignore
+    );
+    let long = format!("verification code 481516 {}", "x".repeat(600));
+    assert_eq!(
+        evaluate(&message("mail", &long, "pipeline-long"), &config),
+        Decision::Ignore
+    );
+    // 长度限制只针对邮件，超过 500 字节的中文短信照常检测
+    let sms = format!(
+        "【示例银行】您的验证码是 592637，{}",
+        "请勿将验证码泄露给他人。".repeat(20)
+    );
+    assert!(sms.len() > 500);
+    assert_eq!(
+        evaluate(&message("imessage", &sms, "pipeline-long-sms"), &config),
+        Decision::Deliver("592637".to_string())
+    );
+
+    let config = MAConfig {
+        float_window: true,
+        ..MAConfig::default()
+    };
+    assert_eq!(
+        evaluate(
+            &message("imessage", "Your code is 236812", "pipeline-float"),
+            &config
+        ),
+        Decision::Show("236812".to_string())
+    );
+}
+
+#[test]
+fn test_float_window_keeps_configured_sinks() {
+    let webhook = SinkConfig::Webhook {
+        url: "http://127.0.0.1:9/hook".to_string(),
+        secret: None,
+    };
+    let mut delivery = HashMap::new();
+    delivery.insert(
+        "sms".to_string(),
+        vec![
+            SinkConfig::Paste,
+            webhook.clone(),
+            SinkConfig::Clipboard,
+            SinkConfig::Notification,
+        ],
+    );
+    let config = MAConfig {
+        float_window: true,
+        delivery,
+        ..MAConfig::default()
+    };
+    assert_eq!(
+        plan(
+            &message("imessage", "Your code is 318204", "pipeline-float-webhook"),
+            &config
+        ),
+        Some((
+            "318204".to_string(),
+            vec![
+                SinkConfig::FloatWindow,
+                webhook.clone(),
+                SinkConfig::Notification
+            ]
+        ))
+    );
+
+    // 不开启浮动窗口时按配置投递
+    let config = MAConfig {
+        float_window: false,
+        ..config
+    };
+    assert_eq!(
+        plan(
+            &message("imessage", "Your code is 318205", "pipeline-webhook"),
+            &config
+        ),
+        Some((
+            "318205".to_string(),
+            vec![
+                SinkConfig::Paste,
+                webhook,
+                SinkConfig::Clipboard,
+                SinkConfig::Notification
+            ]
+        ))
+    );
+}
+
+// 启动前到达、只显示的验证码同样交给命令
+#[test]
+fn test_show_only_keeps_command_hooks() {
+    let hook = SinkConfig::Command(HookConfig {
+        command: "notify-code".to_string(),
+        args: Vec::new(),
+        timeout: 10,
+    });
+    let mut delivery = HashMap::new();
+    delivery.insert("default".to_string(), vec![SinkConfig::Paste, hook.clone()]);
+    let config = MAConfig {
+        catch_up: CatchUpPolicy::Show,
+        delivery,
+        ..MAConfig::default()
+    };
+    mark_activated(unix_now() - 3600);
+    let mut missed = message("imessage", "Your code is 604121", "pipeline-catch-up-hook");
+    missed.received_at = unix_now() - 7200;
+    assert_eq!(
+        plan(&missed, &config),
+        Some((
+            "604121".to_string(),
+            vec![SinkConfig::FloatWindow, hook.clone()]
+        ))
+    );
+    assert_eq!(
+        plan(
+            &message("imessage", "Your code is 604122", "pipeline-hook"),
+            &config
+        ),
+        Some(("604122".to_string(), vec![SinkConfig::Paste, hook]))
+    );
+}
+
+// 依次返回预置的几批信息，之后请求停止
+struct FakeSource {
+    batches: Vec<Vec<IncomingMessage>>,
+}
+
+impl MessageSource for FakeSource {
+    fn name(&self) -> String {
+        "fake".to_string()
+    }
+
+    fn next_messages(&mut self, stop: &StopSignal) -> Result<Vec<IncomingMessage>, Box<dyn Error>> {
+        match self.batches.pop() {
+            Some(batch) => Ok(batch),
+            None => {
+                stop.stop();
+                Ok(Vec::new())
+            }
+        }
+    }
+}
+
+#[test]
+fn test_run_source_hands_every_message_to_pipeline() {
+    let mut source = FakeSource {
+        batches: vec![
+            vec![message("fake", "b", "2"), message("fake", "c", "3")],
+            vec![message("fake", "a", "1")],
+        ],
+    };
+    let mut received = Vec::new();
+    run_source(&mut source, &StopSignal::new(), &mut |m| {
+        received.push(m.body)
+    });
+    assert_eq!(received, vec!["a", "b", "c"]);
+}
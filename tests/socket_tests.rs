@@ -0,0 +1,73 @@
+#![cfg(unix)]
+
+mod common;
+
+use common::temp_dir;
+use std::{
+    fs,
+    io::{BufRead, BufReader, Write},
+    os::unix::{fs::PermissionsExt, net::UnixStream},
+};
+
+use MessAuto::{
+    socket::{parse_line, SocketSource},
+    source::MessageSource,
+    supervisor::StopSignal,
+};
+
+#[test]
+fn test_parse_line() {
+    let message = parse_line(
+        r#"{"body":"Your code is 482913","sender":"portal","source":"cron","timestamp":1714550400}"#,
+    )
+    .unwrap();
+    assert_eq!(message.source, "socket:cron");
+    assert!(!message.is_mail());
+    assert_eq!(message.sender.as_deref(), Some("portal"));
+    assert_eq!(message.received_at, 1714550400);
+
+    let message = parse_line(r#"{"text":"code 123456"}"#).unwrap();
+    assert_eq!(message.source, "socket");
+    assert_eq!(message.body, "code 123456");
+
+    assert!(parse_line(r#"{"sender":"portal"}"#).is_err());
+    assert!(parse_line("code 123456").is_err());
+}
+
+#[test]
+fn test_socket_source() {
+    let dir = temp_dir("socket");
+    let path = dir.join("messauto.sock");
+    // 残留的套接字会被替换
+    drop(std::os::unix::net::UnixListener::bind(&path).unwrap());
+    let mut source = SocketSource::new(&path).unwrap();
+    let mode = fs::metadata(&path).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o600);
+
+    let mut client = UnixStream::connect(&path).unwrap();
+    client
+        .write_all(b"{\"body\":\"Your code is 731946\",\"sender\":\"bridge\"}\nnot json\n")
+        .unwrap();
+    let messages = source.next_messages(&StopSignal::new()).unwrap();
+    assert_eq!(messages.len(), 1);
+    assert_eq!(messages[0].body, "Your code is 731946");
+    assert_eq!(messages[0].sender.as_deref(), Some("bridge"));
+
+    let mut replies = BufReader::new(client.try_clone().unwrap()).lines();
+    assert_eq!(replies.next().unwrap().unwrap(), r#"{"ok":true}"#);
+    assert!(replies.next().unwrap().unwrap().contains(r#""ok":false"#));
+
+    drop(source);
+    assert!(!path.exists());
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn test_socket_source_refuses_regular_file() {
+    let dir = temp_dir("socket-file");
+    let path = dir.join("messauto.sock");
+    fs::write(&path, "not a socket").unwrap();
+    assert!(SocketSource::new(&path).is_err());
+    assert!(path.exists());
+    let _ = fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,125 @@
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use MessAuto::supervisor::{StopSignal, Supervisor};
+
+// 模拟一个监听线程，记录启动次数与同时运行的实例数
+fn worker(
+    started: Arc<AtomicUsize>,
+    active: Arc<AtomicUsize>,
+    max_active: Arc<AtomicUsize>,
+) -> impl FnOnce(StopSignal) + Send + 'static {
+    move |stop| {
+        started.fetch_add(1, Ordering::SeqCst);
+        let now = active.fetch_add(1, Ordering::SeqCst) + 1;
+        max_active.fetch_max(now, Ordering::SeqCst);
+        while stop.sleep(Duration::from_millis(50)) {}
+        active.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+#[test]
+fn test_supervisor_single_instance_and_restart() {
+    let supervisor = Supervisor::new();
+    let started = Arc::new(AtomicUsize::new(0));
+    let active = Arc::new(AtomicUsize::new(0));
+    let max_active = Arc::new(AtomicUsize::new(0));
+    let make = || worker(started.clone(), active.clone(), max_active.clone());
+
+    supervisor.ensure("mail:apple", "a", make());
+    supervisor.ensure("mail:apple", "a", make());
+    thread::sleep(Duration::from_millis(100));
+    assert_eq!(started.load(Ordering::SeqCst), 1);
+    assert!(supervisor.is_running("mail:apple"));
+
+    // 配置变化时先停止旧实例再启动新实例
+    supervisor.ensure("mail:apple", "b", make());
+    thread::sleep(Duration::from_millis(100));
+    assert_eq!(started.load(Ordering::SeqCst), 2);
+    assert_eq!(max_active.load(Ordering::SeqCst), 1);
+
+    supervisor.ensure("mail:imap:Work/INBOX", "x", make());
+    supervisor.ensure("mail:imap:Home/INBOX", "x", make());
+    thread::sleep(Duration::from_millis(100));
+    supervisor.retain("mail:imap:", &["mail:imap:Work/INBOX".to_string()]);
+    assert_eq!(
+        supervisor.running(),
+        vec!["mail:apple", "mail:imap:Work/INBOX"]
+    );
+
+    supervisor.stop("mail:apple");
+    supervisor.retain("mail:", &[]);
+    assert!(supervisor.running().is_empty());
+    // 停止请求是异步的，线程在下一次检查时退出
+    thread::sleep(Duration::from_millis(200));
+    assert_eq!(active.load(Ordering::SeqCst), 0);
+}
+
+#[test]
+fn test_supervisor_does_not_block_on_slow_worker() {
+    let supervisor = Supervisor::new();
+    let started = Arc::new(AtomicUsize::new(0));
+    let active = Arc::new(AtomicUsize::new(0));
+    let max_active = Arc::new(AtomicUsize::new(0));
+    // 收到停止请求后还要 500ms 才退出，例如正在等待网络超时
+    let make = || {
+        let inner = worker(started.clone(), active.clone(), max_active.clone());
+        move |stop: StopSignal| {
+            inner(stop);
+            thread::sleep(Duration::from_millis(500));
+        }
+    };
+
+    supervisor.ensure("mail:imap:Work/INBOX", "a", make());
+    thread::sleep(Duration::from_millis(100));
+
+    let start = Instant::now();
+    supervisor.ensure("mail:imap:Work/INBOX", "b", make());
+    assert!(start.elapsed() < Duration::from_millis(200));
+    assert!(supervisor.is_running("mail:imap:Work/INBOX"));
+
+    // 新实例等旧实例退出后才开始运行
+    thread::sleep(Duration::from_millis(200));
+    assert_eq!(started.load(Ordering::SeqCst), 1);
+    thread::sleep(Duration::from_millis(600));
+    assert_eq!(started.load(Ordering::SeqCst), 2);
+    assert_eq!(max_active.load(Ordering::SeqCst), 1);
+
+    let start = Instant::now();
+    supervisor.stop("mail:imap:Work/INBOX");
+    assert!(start.elapsed() < Duration::from_millis(200));
+
+    // 停止后立即再次启动，同样要等旧实例退出
+    supervisor.ensure("mail:imap:Work/INBOX", "b", make());
+    thread::sleep(Duration::from_millis(200));
+    assert_eq!(started.load(Ordering::SeqCst), 2);
+    thread::sleep(Duration::from_millis(600));
+    assert_eq!(started.load(Ordering::SeqCst), 3);
+    assert_eq!(max_active.load(Ordering::SeqCst), 1);
+    supervisor.stop("mail:imap:Work/INBOX");
+}
+
+#[test]
+fn test_supervisor_restarts_finished_worker() {
+    let supervisor = Supervisor::new();
+    let started = Arc::new(AtomicUsize::new(0));
+    let counter = started.clone();
+    supervisor.ensure("mail:local", "a", move |_| {
+        counter.fetch_add(1, Ordering::SeqCst);
+    });
+    thread::sleep(Duration::from_millis(100));
+    assert!(!supervisor.is_running("mail:local"));
+
+    let counter = started.clone();
+    supervisor.ensure("mail:local", "a", move |_| {
+        counter.fetch_add(1, Ordering::SeqCst);
+    });
+    thread::sleep(Duration::from_millis(100));
+    assert_eq!(started.load(Ordering::SeqCst), 2);
+}
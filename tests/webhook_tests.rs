@@ -0,0 +1,253 @@
+use std::{
+    collections::HashMap,
+    io::{Read, Write},
+    net::TcpStream,
+    sync::mpsc,
+    thread,
+    time::{Duration, Instant},
+};
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use MessAuto::{
+    source::MessageSource,
+    supervisor::StopSignal,
+    webhook::{
+        authenticate, parse_payload, RateLimiter, WebhookConfig, WebhookSource, MAX_CONNECTIONS,
+    },
+};
+
+const SECRET: &str = "shared-secret";
+
+fn sign(body: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(SECRET.as_bytes()).unwrap();
+    mac.update(body.as_bytes());
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+// 发送一个 POST 请求并返回状态码
+fn post(port: u16, content_type: &str, headers: &[(&str, String)], body: &str) -> u16 {
+    let mut stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+    let mut request = format!(
+        "POST /sms HTTP/1.1\r\nHost: localhost\r\nContent-Type: {}\r\nContent-Length: {}\r\n",
+        content_type,
+        body.len()
+    );
+    for (name, value) in headers {
+        request.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    request.push_str("\r\n");
+    request.push_str(body);
+    stream.write_all(request.as_bytes()).unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    response.split_whitespace().nth(1).unwrap().parse().unwrap()
+}
+
+fn start(rate_limit: usize) -> (u16, mpsc::Receiver<MessAuto::source::IncomingMessage>) {
+    let config = WebhookConfig {
+        address: "127.0.0.1:0".to_string(),
+        secret: None,
+        rate_limit,
+    };
+    let mut source = WebhookSource::new(&config, SECRET.to_string()).unwrap();
+    let port = source.local_port().unwrap();
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let stop = StopSignal::new();
+        while let Ok(messages) = source.next_messages(&stop) {
+            for message in messages {
+                if tx.send(message).is_err() {
+                    return;
+                }
+            }
+        }
+    });
+    (port, rx)
+}
+
+#[test]
+fn test_authenticate() {
+    let body = br#"{"body":"code 123456"}"#;
+    let mut headers = HashMap::new();
+    assert!(!authenticate(SECRET, &headers, body));
+    headers.insert(
+        "x-messauto-signature".to_string(),
+        sign(std::str::from_utf8(body).unwrap()),
+    );
+    assert!(authenticate(SECRET, &headers, body));
+    assert!(!authenticate(SECRET, &headers, b"tampered"));
+    let mut headers = HashMap::new();
+    headers.insert("x-messauto-secret".to_string(), SECRET.to_string());
+    assert!(authenticate(SECRET, &headers, body));
+    headers.insert("x-messauto-secret".to_string(), "wrong".to_string());
+    assert!(!authenticate(SECRET, &headers, body));
+
+    // 空密钥不接受任何请求，也不会被当作已配置
+    headers.insert("x-messauto-secret".to_string(), String::new());
+    assert!(!authenticate("", &headers, body));
+    let config = WebhookConfig {
+        secret: Some(String::new()),
+        ..WebhookConfig::default()
+    };
+    assert_ne!(config.secret().as_deref(), Some(""));
+}
+
+#[test]
+fn test_parse_payload() {
+    let message = parse_payload(
+        "application/json",
+        br#"{"from":"+15550100","text":"Your code is 482913","timestamp":1714550400000}"#,
+    )
+    .unwrap();
+    assert_eq!(message.source, "webhook");
+    assert!(!message.is_mail());
+    assert_eq!(message.sender.as_deref(), Some("+15550100"));
+    assert_eq!(message.body, "Your code is 482913");
+    assert_eq!(message.received_at, 1714550400);
+
+    let message = parse_payload(
+        "application/x-www-form-urlencoded; charset=utf-8",
+        b"sender=%2B15550100&body=%E9%AA%8C%E8%AF%81%E7%A0%81+731946&timestamp=1714550400",
+    )
+    .unwrap();
+    assert_eq!(message.sender.as_deref(), Some("+15550100"));
+    assert_eq!(message.body, "验证码 731946");
+    assert_eq!(message.received_at, 1714550400);
+
+    assert!(parse_payload("application/json", br#"{"sender":"x"}"#).is_none());
+    assert!(parse_payload("text/plain", b"code 123456").is_none());
+}
+
+#[test]
+fn test_rate_limiter() {
+    let mut limiter = RateLimiter::new(2, Duration::from_secs(60));
+    let now = Instant::now();
+    assert!(limiter.allow(now));
+    assert!(limiter.allow(now));
+    assert!(!limiter.allow(now + Duration::from_secs(30)));
+    assert!(limiter.allow(now + Duration::from_secs(61)));
+}
+
+#[test]
+fn test_webhook_source_accepts_authenticated_requests() {
+    let (port, rx) = start(30);
+
+    let body = r#"{"sender":"+15550100","body":"Your verification code is 731946"}"#;
+    assert_eq!(post(port, "application/json", &[], body), 401);
+    assert_eq!(
+        post(
+            port,
+            "application/json",
+            &[("X-MessAuto-Signature", sign("tampered"))],
+            body
+        ),
+        401
+    );
+    assert_eq!(
+        post(
+            port,
+            "application/json",
+            &[("X-MessAuto-Signature", sign(body))],
+            body
+        ),
+        202
+    );
+    let message = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert_eq!(message.body, "Your verification code is 731946");
+    assert_eq!(message.sender.as_deref(), Some("+15550100"));
+
+    let form = "from=10690000&content=code+123456";
+    assert_eq!(
+        post(
+            port,
+            "application/x-www-form-urlencoded",
+            &[("X-MessAuto-Secret", SECRET.to_string())],
+            form
+        ),
+        202
+    );
+    let message = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert_eq!(message.body, "code 123456");
+
+    // 认证通过但缺少正文
+    assert_eq!(
+        post(
+            port,
+            "application/json",
+            &[("X-MessAuto-Secret", SECRET.to_string())],
+            "{}"
+        ),
+        400
+    );
+    assert!(rx.try_recv().is_err());
+}
+
+#[test]
+fn test_webhook_source_rate_limits() {
+    let (port, _rx) = start(2);
+    let body = r#"{"body":"code 123456"}"#;
+    let secret = [("X-MessAuto-Secret", SECRET.to_string())];
+    assert_eq!(post(port, "application/json", &secret, body), 202);
+    assert_eq!(post(port, "application/json", &[], body), 401);
+    assert_eq!(post(port, "application/json", &secret, body), 429);
+}
+
+#[test]
+fn test_webhook_slow_client_does_not_block_others() {
+    let (port, rx) = start(30);
+    // 只发送一半请求头后停住的客户端
+    let mut slow = TcpStream::connect(("127.0.0.1", port)).unwrap();
+    slow.write_all(b"POST /sms HTTP/1.1\r\nContent-Length: 10\r\n")
+        .unwrap();
+    thread::sleep(Duration::from_millis(200));
+
+    let start = Instant::now();
+    let body = r#"{"body":"code 123456"}"#;
+    let secret = [("X-MessAuto-Secret", SECRET.to_string())];
+    assert_eq!(post(port, "application/json", &secret, body), 202);
+    assert!(start.elapsed() < Duration::from_secs(2));
+    assert_eq!(
+        rx.recv_timeout(Duration::from_secs(2)).unwrap().body,
+        "code 123456"
+    );
+
+    // 超过总时限后慢速客户端的连接被关闭
+    slow.set_read_timeout(Some(Duration::from_secs(10)))
+        .unwrap();
+    let mut response = Vec::new();
+    let _ = slow.read_to_end(&mut response);
+    assert!(start.elapsed() < Duration::from_secs(8));
+}
+
+#[test]
+fn test_webhook_limits_open_connections() {
+    let (port, _rx) = start(30);
+    // 占满连接名额后停住的客户端
+    let slow: Vec<_> = (0..MAX_CONNECTIONS)
+        .map(|_| {
+            let mut stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+            stream.write_all(b"POST /sms HTTP/1.1\r\n").unwrap();
+            stream
+        })
+        .collect();
+    thread::sleep(Duration::from_millis(200));
+
+    // 超出的连接不等待请求，立即收到 429
+    let start = Instant::now();
+    let mut excess = TcpStream::connect(("127.0.0.1", port)).unwrap();
+    excess
+        .set_read_timeout(Some(Duration::from_secs(2)))
+        .unwrap();
+    let mut response = String::new();
+    excess.read_to_string(&mut response).unwrap();
+    assert!(response.starts_with("HTTP/1.1 429"), "{}", response);
+    assert!(start.elapsed() < Duration::from_secs(1));
+
+    // 慢速客户端断开后名额释放
+    drop(slow);
+    thread::sleep(Duration::from_millis(200));
+    let body = r#"{"body":"code 123456"}"#;
+    let secret = [("X-MessAuto-Secret", SECRET.to_string())];
+    assert_eq!(post(port, "application/json", &secret, body), 202);
+}